@@ -6,6 +6,7 @@ rustler::atoms! {
     dbname,
     username,
     password,
+    token,
     ts_column,
     ttl,
     tls,
@@ -16,10 +17,173 @@ rustler::atoms! {
     client_cert,
     client_key,
     cipher_suites,
+    tls_config,
+    fips_mode,
+    auto,
+    required,
+    off,
+    default_timestamp,
+    now,
+    column,
+    coercion,
+    strict,
+    widen,
+    stringify,
+    null_on_error,
+    schema_hints,
+    tag,
+    field,
+    balance,
+    round_robin,
+    least_latency,
+    health_check_interval,
+    health_check_timeout,
+    proxy,
+    url,
+    no_proxy,
+    connect_timeout,
+    request_timeout,
+    keepalive_interval,
+    keepalive_timeout,
+    keepalive_while_idle,
+    tcp_keepalive,
+    max_send_message_size,
+    max_recv_message_size,
+    initial_stream_window_size,
+    initial_connection_window_size,
+    otlp_endpoint,
+    compression,
+    none,
+    gzip,
+    zstd,
+    retry,
+    max_attempts,
+    initial_backoff,
+    max_backoff,
+    jitter,
+    retry_on,
+    deadline,
+    unavailable,
+    deadline_exceeded,
+    resource_exhausted,
+    aborted,
+    cancelled,
+    internal,
+
+    // Connection status
+    endpoint,
+    status,
+    up,
+    down,
+    unknown,
+    latency_us,
+    last_error,
+    last_check,
+    cipher_suite,
+    fips,
+    version,
+    timeout,
+
+    // Insert options
+    partial_write,
+    traceparent,
+    tracestate,
+    schema_conflict,
+    attempts,
+    idempotency_key,
+    db,
+    deduplicated,
+    in_progress,
+
+    // Stats
+    inserts,
+    queries,
+    deletes,
+    writes,
+    rows_written,
+    rows_rejected,
+    bytes_sent,
+    retries,
+    schema_fetches,
+    errors,
+    conversion,
+    server,
+    in_flight,
+    streams_open,
+    insert_latency_us,
+    query_latency_us,
+    delete_latency_us,
+    schema_fetch_latency_us,
+    write_latency_us,
+    buckets,
+    count,
+    sum,
+    infinity,
 
     // FIPS status
     fips_enabled,
 
+    // Logging
+    greptimedb_rs_log,
+    max_per_second,
+    warning,
+    info,
+    debug,
+    trace,
+    target,
+    module_path,
+    file,
+    line,
+
+    // Buffered writer
+    greptimedb_rs_flush,
+    max_rows,
+    max_bytes,
+    linger,
+    notify,
+
+    // Spool
+    spool,
+    dir,
+    segment_bytes,
+    eviction,
+    drop_oldest,
+    reject_new,
+    replay_interval,
+    spooled,
+    records,
+    bytes,
+    segments,
+    replayed,
+    evicted,
+    discarded,
+    corrupt,
+
+    // Deduplication
+    dedup,
+    window,
+    capacity,
+
+    // In-flight limits
+    max_in_flight,
+    stream_max_in_flight,
+    requests,
+    in_flight_bytes,
+    overloaded,
+
+    // Table description
+    name,
+    data_type,
+    semantic_type,
+    nullable,
+    default,
+    indexes,
+    time_index,
+    primary_key,
+    inverted,
+    fulltext,
+    skipping,
+
     // Rows
     fields,
     tags,
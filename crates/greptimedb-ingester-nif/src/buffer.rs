@@ -0,0 +1,340 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use greptimedb_ingester::api::v1::{ColumnSchema, Row, RowInsertRequest, RowInsertRequests, Rows};
+use greptimedb_ingester::database::Database;
+use greptimedb_ingester::TableSchema;
+use rustler::{Encoder, LocalPid, OwnedEnv};
+use tokio::runtime::{Handle, Runtime};
+use tokio::task::JoinSet;
+
+use crate::limits::Permit;
+use crate::metrics::{self, ConnectionMetrics};
+use crate::retry::{self, RetryPolicy};
+use crate::spool::{self, Spool, Written};
+use crate::{atoms, health, GreptimeResource, SharedAuth};
+
+pub const DEFAULT_MAX_ROWS: usize = 5000;
+pub const DEFAULT_MAX_BYTES: usize = 4 * 1024 * 1024;
+pub const DEFAULT_LINGER: Duration = Duration::from_secs(1);
+
+/// When the rows of a table are flushed, whichever comes first.
+#[derive(Clone, Debug)]
+pub struct BufferOptions {
+    pub max_rows: usize,
+    /// Encoded size of the rows.
+    pub max_bytes: usize,
+    /// Age of the oldest row.
+    pub linger: Duration,
+    /// Receives `{greptimedb_rs_flush, Table, {ok, Affected | spooled} | {error, Reason}}`
+    /// for each flush.
+    pub notify: Option<LocalPid>,
+}
+
+/// What a flush needs from the connection.
+///
+/// The tasks don't keep the connection itself: releasing its last reference there would
+/// shut its runtime down from within.
+struct Sink {
+    endpoints: Arc<health::Endpoints>,
+    dbname: String,
+    auth: SharedAuth,
+    ttl: Option<String>,
+    retry: Option<RetryPolicy>,
+    metrics: Arc<ConnectionMetrics>,
+    spool: Option<Arc<Spool>>,
+}
+
+impl Sink {
+    fn database(&self) -> Database {
+        let auth = self.auth.read().unwrap().clone();
+        self.endpoints.pick().database(&self.dbname, auth)
+    }
+
+    /// Insert the rows of a table, as `insert` does.
+    async fn send(&self, table: &str, buffer: TableBuffer) -> Result<Written, String> {
+        let request = RowInsertRequests {
+            inserts: buffer
+                .chunks
+                .into_iter()
+                .map(|rows| RowInsertRequest {
+                    table_name: table.to_string(),
+                    rows: Some(rows),
+                })
+                .collect(),
+        };
+        // Queue behind the spooled requests, to keep them in order
+        if let Some(spool) = self.spool.as_ref().filter(|spool| spool.pending() > 0) {
+            return push(spool, request).await.map(|()| Written::Spooled);
+        }
+
+        let metrics = &self.metrics;
+        metrics.inserts.fetch_add(1, Ordering::Relaxed);
+        let _in_flight = metrics::Gauge::inc(&metrics.in_flight);
+        let started = Instant::now();
+        let ttl = self.ttl.as_deref();
+        let (result, attempts) = retry::run(self.retry.as_ref(), || {
+            let db = self.database();
+            let request = request.clone();
+            metrics.bytes_sent.fetch_add(
+                prost::Message::encoded_len(&request) as u64,
+                Ordering::Relaxed,
+            );
+            async move {
+                match ttl {
+                    Some(ttl) => db.insert_with_hints(request, &[("ttl", ttl)]).await,
+                    None => db.insert(request).await,
+                }
+                .map_err(retry::Failure::from)
+            }
+        })
+        .await;
+
+        metrics.insert_latency.observe(started.elapsed());
+        metrics
+            .retries
+            .fetch_add(attempts.saturating_sub(1) as u64, Ordering::Relaxed);
+        match result {
+            Ok(affected) => {
+                metrics
+                    .rows_written
+                    .fetch_add(affected as u64, Ordering::Relaxed);
+                Ok(Written::Affected(affected))
+            }
+            Err(e) => {
+                metrics.errors.record_err(&e);
+                if let Some(spool) = self.spool.as_ref().filter(|_| spool::recoverable(&e)) {
+                    if push(spool, request).await.is_ok() {
+                        return Ok(Written::Spooled);
+                    }
+                }
+                log::warn!("flush of {} rows to `{table}` failed: {e}", buffer.rows);
+                Err(e.message)
+            }
+        }
+    }
+}
+
+/// Spool a request, off the runtime's workers since it writes and syncs a file.
+async fn push(spool: &Arc<Spool>, request: RowInsertRequests) -> Result<(), String> {
+    let spool = spool.clone();
+    tokio::task::spawn_blocking(move || spool.push(&request))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Rows of a table waiting to be flushed.
+struct TableBuffer {
+    /// Rows converted to the same schema are sent together.
+    chunks: Vec<Rows>,
+    rows: usize,
+    bytes: usize,
+    since: Instant,
+    /// Room the rows take in the connection's in-flight limits, until they are sent.
+    permit: Permit,
+}
+
+struct State {
+    tables: HashMap<String, TableBuffer>,
+    /// `None` for tables that don't exist yet, looked up again once they are flushed.
+    schemas: HashMap<String, Option<TableSchema>>,
+    closed: bool,
+}
+
+impl State {
+    fn take(&mut self, table: &str) -> Option<TableBuffer> {
+        // The flush creates the table
+        if let Some(None) = self.schemas.get(table) {
+            self.schemas.remove(table);
+        }
+        self.tables.remove(table)
+    }
+}
+
+/// Rows accumulated per table, sent from the connection runtime.
+pub struct Buffer {
+    opts: BufferOptions,
+    sink: Arc<Sink>,
+    state: Mutex<State>,
+    flushes: Mutex<JoinSet<()>>,
+    runtime: Arc<Runtime>,
+}
+
+impl Buffer {
+    /// The buffer, with a task flushing the tables whose rows are older than `linger`
+    /// until it is closed or dropped.
+    pub fn start(connection: &GreptimeResource, opts: BufferOptions) -> Arc<Self> {
+        let tick = (opts.linger / 4).max(Duration::from_millis(10));
+        let buffer = Arc::new(Buffer {
+            opts,
+            sink: Arc::new(Sink {
+                endpoints: connection.endpoints.clone(),
+                dbname: connection.dbname.clone(),
+                auth: connection.auth.clone(),
+                ttl: connection.ttl.clone(),
+                retry: connection.retry.clone(),
+                metrics: connection.metrics.clone(),
+                spool: connection.spool.clone(),
+            }),
+            state: Mutex::new(State {
+                tables: HashMap::new(),
+                schemas: HashMap::new(),
+                closed: false,
+            }),
+            flushes: Mutex::new(JoinSet::new()),
+            runtime: connection.runtime.clone(),
+        });
+
+        let weak = Arc::downgrade(&buffer);
+        connection.runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(tick);
+            loop {
+                ticker.tick().await;
+                match weak.upgrade() {
+                    Some(buffer) if buffer.flush_expired() => {}
+                    _ => break,
+                }
+            }
+        });
+        buffer
+    }
+
+    /// The cached schema of `table`, `None` when it was not looked up yet.
+    pub fn schema(&self, table: &str) -> Option<Option<TableSchema>> {
+        self.state.lock().unwrap().schemas.get(table).cloned()
+    }
+
+    pub fn cache_schema(&self, table: &str, schema: Option<TableSchema>) {
+        let mut state = self.state.lock().unwrap();
+        state.schemas.insert(table.to_string(), schema);
+    }
+
+    /// Buffer converted rows, flushing the table once it holds `max_rows` or `max_bytes`.
+    pub fn append(
+        &self,
+        table: String,
+        schema: Vec<ColumnSchema>,
+        rows: Vec<Row>,
+        permit: Permit,
+    ) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err("writer is closed".to_string());
+        }
+
+        let count = rows.len();
+        let bytes: usize = rows.iter().map(prost::Message::encoded_len).sum();
+        let buffer = match state.tables.entry(table.clone()) {
+            Entry::Occupied(entry) => {
+                let buffer = entry.into_mut();
+                buffer.permit.merge(permit);
+                buffer
+            }
+            Entry::Vacant(entry) => entry.insert(TableBuffer {
+                chunks: Vec::new(),
+                rows: 0,
+                bytes: 0,
+                since: Instant::now(),
+                permit,
+            }),
+        };
+        match buffer.chunks.last_mut() {
+            Some(chunk) if chunk.schema == schema => chunk.rows.extend(rows),
+            _ => buffer.chunks.push(Rows { schema, rows }),
+        }
+        buffer.rows += count;
+        buffer.bytes += bytes;
+
+        if buffer.rows >= self.opts.max_rows || buffer.bytes >= self.opts.max_bytes {
+            if let Some(buffer) = state.take(&table) {
+                self.spawn_flush(table, buffer);
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush every table now. Returns `false` once the buffer is closed.
+    pub fn flush(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let tables: Vec<String> = state.tables.keys().cloned().collect();
+        for table in tables {
+            if let Some(buffer) = state.take(&table) {
+                self.spawn_flush(table, buffer);
+            }
+        }
+        !state.closed
+    }
+
+    /// Flush the tables older than `linger`. Returns `false` once the buffer is closed.
+    fn flush_expired(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let expired: Vec<String> = state
+            .tables
+            .iter()
+            .filter(|(_, buffer)| buffer.since.elapsed() >= self.opts.linger)
+            .map(|(table, _)| table.clone())
+            .collect();
+        for table in expired {
+            if let Some(buffer) = state.take(&table) {
+                self.spawn_flush(table, buffer);
+            }
+        }
+        !state.closed
+    }
+
+    /// Flush every table and wait for all flushes, appends fail afterwards.
+    pub async fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.flush();
+        let mut flushes = std::mem::take(&mut *self.flushes.lock().unwrap());
+        while flushes.join_next().await.is_some() {}
+    }
+
+    fn spawn_flush(&self, table: String, buffer: TableBuffer) {
+        let sink = self.sink.clone();
+        let notify = self.opts.notify;
+        let mut flushes = self.flushes.lock().unwrap();
+        // Reap the finished ones, their results were reported already
+        while flushes.try_join_next().is_some() {}
+        flushes.spawn_on(
+            async move {
+                let result = sink.send(&table, buffer).await;
+                if let Some(pid) = notify {
+                    // The process may be gone, nothing to report it to
+                    let _ = OwnedEnv::new().send_and_clear(&pid, |env| {
+                        let result = match result {
+                            Ok(written) => (atoms::ok(), written).encode(env),
+                            Err(e) => (atoms::error(), e).encode(env),
+                        };
+                        (atoms::greptimedb_rs_flush(), table.as_str(), result).encode(env)
+                    });
+                }
+            },
+            self.runtime.handle(),
+        );
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        // Not closed with `buffer_close`, send what is left
+        let tables = std::mem::take(&mut self.state.get_mut().unwrap().tables);
+        for (table, buffer) in tables {
+            self.spawn_flush(table, buffer);
+        }
+        let mut flushes = std::mem::take(self.flushes.get_mut().unwrap());
+        // Dropping the set would abort the flushes, and so would the runtime shutting down
+        // with the connection. They are waited for on a thread keeping the runtime, which
+        // also releases it when the buffer goes from one of its tasks.
+        if flushes.is_empty() && Handle::try_current().is_err() {
+            return;
+        }
+        let runtime = self.runtime.clone();
+        std::thread::spawn(move || {
+            runtime.block_on(async { while flushes.join_next().await.is_some() {} });
+        });
+    }
+}
@@ -0,0 +1,93 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::TryFutureExt;
+use hyper_util::rt::TokioIo;
+use rustls::pki_types::ServerName;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tonic::codegen::http::Uri;
+use tonic::codegen::Service;
+
+use crate::proxy::ProxyConfig;
+use crate::tls;
+
+/// A connection of a channel, plain or TLS.
+pub trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+/// Opens the connections of a channel to the host of its endpoint, directly or through the
+/// proxy, then runs the TLS handshake with the connection's own settings. The server name
+/// checked is always the endpoint's, and certificates never go through files.
+#[derive(Clone)]
+pub struct Connector {
+    tls: Option<Arc<rustls::ClientConfig>>,
+    proxy: Option<Arc<ProxyConfig>>,
+    /// The cipher suite of the last handshake, by IANA name.
+    cipher_suite: Arc<Mutex<Option<String>>>,
+}
+
+impl Connector {
+    pub fn new(tls: Option<Arc<rustls::ClientConfig>>, proxy: Option<Arc<ProxyConfig>>) -> Self {
+        Connector {
+            tls,
+            proxy,
+            cipher_suite: Default::default(),
+        }
+    }
+
+    pub fn cipher_suite(&self) -> Option<String> {
+        self.cipher_suite.lock().unwrap().clone()
+    }
+
+    /// The handshakes run on a FIPS crypto provider.
+    pub fn fips(&self) -> bool {
+        self.tls.as_ref().is_some_and(|tls| tls.fips())
+    }
+
+    async fn connect(self, uri: Uri) -> io::Result<Box<dyn Io>> {
+        let host = uri
+            .host()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no host in uri"))?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let port = uri.port_u16().unwrap_or(80);
+
+        let stream = match &self.proxy {
+            Some(proxy) if !proxy.bypass(host) => proxy.connect(host, port).await?,
+            _ => {
+                let stream = TcpStream::connect((host, port)).await?;
+                stream.set_nodelay(true)?;
+                stream
+            }
+        };
+        let Some(tls) = self.tls else {
+            return Ok(Box::new(stream));
+        };
+        let server_name = ServerName::try_from(host.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let stream = TlsConnector::from(tls).connect(server_name, stream).await?;
+        if let Some(suite) = stream.get_ref().1.negotiated_cipher_suite() {
+            *self.cipher_suite.lock().unwrap() = Some(tls::iana_name(suite.suite()));
+        }
+        Ok(Box::new(stream))
+    }
+}
+
+impl Service<Uri> for Connector {
+    type Response = TokioIo<Box<dyn Io>>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        Box::pin(self.clone().connect(uri).map_ok(TokioIo::new))
+    }
+}
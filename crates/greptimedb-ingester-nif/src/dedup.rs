@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(300);
+pub const DEFAULT_CAPACITY: usize = 100_000;
+
+/// How long and how many acknowledged keys are remembered, the least recently used
+/// forgotten first. `capacity` also bounds the keys being sent.
+#[derive(Clone, Debug)]
+pub struct DedupOptions {
+    pub window: Duration,
+    pub capacity: usize,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        DedupOptions {
+            window: DEFAULT_WINDOW,
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+/// Idempotency key of a batch, scoped to its table.
+type Key = (String, String);
+
+enum Entry {
+    /// Sent and not answered yet.
+    Pending,
+    Acked {
+        affected: u32,
+        at: Instant,
+        /// When it was last claimed, its place in `State::lru`.
+        used: u64,
+    },
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<Key, Entry>,
+    /// Acknowledged keys, least recently used first.
+    lru: BTreeMap<u64, Key>,
+    pending: usize,
+    /// Last use given out.
+    clock: u64,
+}
+
+impl State {
+    fn forget(&mut self, key: &Key) {
+        if let Some(Entry::Acked { used, .. }) = self.entries.remove(key) {
+            self.lru.remove(&used);
+        }
+    }
+}
+
+/// What to do with a batch carrying an idempotency key.
+pub enum Claim {
+    /// First seen, to be sent and then acknowledged with the ticket.
+    New(Ticket),
+    /// Acknowledged already, with the rows it wrote.
+    Done(u32),
+    /// Being sent by another call.
+    InProgress,
+    /// As many keys as the capacity are being sent already.
+    Full,
+}
+
+/// The idempotency keys of the batches written to a database, shared by the connections
+/// to it so a resend is recognised whichever connection it goes through.
+pub struct Dedup {
+    opts: DedupOptions,
+    state: Mutex<State>,
+    /// Resends skipped.
+    hits: AtomicU64,
+}
+
+static OPEN: Mutex<Vec<(String, Weak<Dedup>)>> = Mutex::new(Vec::new());
+
+impl Dedup {
+    /// The keys of the database named `scope`. Those already tracked keep their options.
+    pub fn shared(scope: String, opts: DedupOptions) -> Arc<Self> {
+        let mut open = OPEN.lock().unwrap();
+        open.retain(|(_, dedup)| dedup.strong_count() > 0);
+        if let Some(dedup) = open
+            .iter()
+            .find(|(s, _)| *s == scope)
+            .and_then(|(_, dedup)| dedup.upgrade())
+        {
+            return dedup;
+        }
+
+        let dedup = Arc::new(Dedup {
+            opts,
+            state: Mutex::new(State::default()),
+            hits: AtomicU64::new(0),
+        });
+        open.push((scope, Arc::downgrade(&dedup)));
+        dedup
+    }
+
+    pub fn claim(self: &Arc<Self>, table: &str, key: String) -> Claim {
+        let mut state = self.state.lock().unwrap();
+        let key = (table.to_string(), key);
+        state.clock += 1;
+        let clock = state.clock;
+        let State { entries, lru, .. } = &mut *state;
+        match entries.get_mut(&key) {
+            Some(Entry::Acked { affected, at, used }) if at.elapsed() < self.opts.window => {
+                lru.remove(used);
+                lru.insert(clock, key);
+                *used = clock;
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Claim::Done(*affected);
+            }
+            // Expired
+            Some(Entry::Acked { used, .. }) => {
+                lru.remove(used);
+                entries.remove(&key);
+            }
+            Some(Entry::Pending) => return Claim::InProgress,
+            None => {}
+        }
+        if state.pending >= self.opts.capacity {
+            return Claim::Full;
+        }
+        state.entries.insert(key.clone(), Entry::Pending);
+        state.pending += 1;
+        Claim::New(Ticket {
+            dedup: self.clone(),
+            key: Some(key),
+        })
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Forget the least recently used keys over the capacity, and those older than the
+    /// window among them. The others expire when claimed again.
+    fn evict(&self, state: &mut State) {
+        while let Some((_, key)) = state.lru.first_key_value() {
+            let expired = match state.entries.get(key) {
+                Some(Entry::Acked { at, .. }) => at.elapsed() >= self.opts.window,
+                _ => true,
+            };
+            if !expired && state.lru.len() <= self.opts.capacity {
+                break;
+            }
+            let key = key.clone();
+            state.forget(&key);
+        }
+    }
+}
+
+/// A key claimed by a call. Dropped without being acknowledged, the batch may be sent again.
+pub struct Ticket {
+    dedup: Arc<Dedup>,
+    key: Option<Key>,
+}
+
+impl Ticket {
+    /// The batch was written, resends are skipped from now on.
+    pub fn ack(mut self, affected: u32) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        let mut state = self.dedup.state.lock().unwrap();
+        state.pending -= 1;
+        state.clock += 1;
+        let used = state.clock;
+        state.lru.insert(used, key.clone());
+        state.entries.insert(
+            key,
+            Entry::Acked {
+                affected,
+                at: Instant::now(),
+                used,
+            },
+        );
+        self.dedup.evict(&mut state);
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let mut state = self.dedup.state.lock().unwrap();
+            state.pending -= 1;
+            state.entries.remove(&key);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use greptimedb_ingester::channel_manager::TlsVerify;
+
+use crate::atoms;
+use crate::tls::{ConfigError, TlsConfig};
+
+const FIPS_ENABLED_FILE: &str = "/proc/sys/crypto/fips_enabled";
+/// Overrides `FIPS_ENABLED_FILE`, so tests can fake the host state.
+const FIPS_ENABLED_FILE_ENV: &str = "GREPTIMEDB_RS_FIPS_ENABLED_FILE";
+
+static FIPS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Approved for FIPS 140-3, by IANA name.
+pub const CIPHER_SUITES: &[&str] = &[
+    "TLS_AES_256_GCM_SHA384",
+    "TLS_AES_128_GCM_SHA256",
+    "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+    "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+    "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+    "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FipsMode {
+    /// Enforce when the host runs in FIPS mode.
+    #[default]
+    Auto,
+    /// Always enforce, and fail without a FIPS-capable crypto provider.
+    Required,
+    Off,
+}
+
+impl FipsMode {
+    pub fn enforced(self) -> bool {
+        match self {
+            FipsMode::Auto => is_enabled(),
+            FipsMode::Required => true,
+            FipsMode::Off => false,
+        }
+    }
+}
+
+/// The host state as of the last `detect`.
+pub fn is_enabled() -> bool {
+    FIPS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Read the host state again.
+pub fn detect() -> bool {
+    let path =
+        std::env::var(FIPS_ENABLED_FILE_ENV).unwrap_or_else(|_| FIPS_ENABLED_FILE.to_string());
+    let enabled = std::fs::read_to_string(path)
+        .map(|s| s.trim() == "1")
+        .unwrap_or(false);
+    FIPS_ENABLED.store(enabled, Ordering::Relaxed);
+    enabled
+}
+
+/// Reject settings not allowed in FIPS mode, and restrict the default cipher suites
+/// to the approved ones.
+pub fn enforce(config: &mut TlsConfig) -> Result<(), ConfigError> {
+    if matches!(config.verify, TlsVerify::VerifyNone) {
+        return Err(ConfigError::new(
+            atoms::verify(),
+            "verify_none is not allowed in FIPS mode",
+        ));
+    }
+    if config.cipher_suites.is_empty() {
+        config.cipher_suites = CIPHER_SUITES.iter().map(|s| s.to_string()).collect();
+    } else if let Some(name) = config
+        .cipher_suites
+        .iter()
+        .find(|name| !CIPHER_SUITES.contains(&name.as_str()))
+    {
+        return Err(ConfigError::new(
+            atoms::cipher_suites(),
+            format!("`{name}` is not FIPS-approved"),
+        ));
+    }
+    Ok(())
+}
+
+/// Make the FIPS crypto provider the process default.
+///
+/// Only available when built with the `fips` feature. Without it, `required` fails while
+/// `auto` keeps the default provider with the restricted settings, with a warning. The
+/// provider in use is reported by `connection_status`.
+pub fn install_provider(mode: FipsMode) -> Result<(), ConfigError> {
+    match (try_install_provider(), mode) {
+        (Ok(()), _) => Ok(()),
+        (Err(e), FipsMode::Required) => Err(ConfigError::new(atoms::fips_mode(), e)),
+        (Err(e), _) => {
+            log::warn!(
+                "host is in FIPS mode but the FIPS crypto provider is not available ({e}), \
+                 TLS is restricted to the approved settings on the default provider"
+            );
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "fips")]
+fn try_install_provider() -> Result<(), String> {
+    use rustls::crypto::CryptoProvider;
+
+    // Fails when another provider won the race, checked below
+    let _ = rustls::crypto::default_fips_provider().install_default();
+    match CryptoProvider::get_default() {
+        Some(provider) if provider.fips() => Ok(()),
+        _ => Err("a non-FIPS crypto provider is already installed".to_string()),
+    }
+}
+
+#[cfg(not(feature = "fips"))]
+fn try_install_provider() -> Result<(), String> {
+    Err("built without the `fips` feature".to_string())
+}
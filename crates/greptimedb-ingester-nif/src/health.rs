@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures::StreamExt;
+use greptime_proto::v1::auth_header::AuthScheme;
+use greptimedb_ingester::channel_manager::ChannelManager;
+use greptimedb_ingester::client::Client;
+use greptimedb_ingester::database::Database;
+use rustler::{Encoder, Env, Term};
+use tokio::runtime::{Handle, Runtime};
+
+use crate::connector::Connector;
+use crate::{atoms, SharedAuth};
+
+/// How requests are spread over the healthy endpoints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Balance {
+    #[default]
+    RoundRobin,
+    /// The endpoint with the lowest health check latency.
+    LeastLatency,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Health {
+    /// Not checked yet, treated as up.
+    #[default]
+    Unknown,
+    Up,
+    Down,
+}
+
+#[derive(Default)]
+struct EndpointState {
+    health: Health,
+    latency: Option<Duration>,
+    last_error: Option<String>,
+    last_check: Option<SystemTime>,
+}
+
+/// The channel manager drops channels idle for a minute and would make them again without
+/// the connector, so channels idle for this long are made again before use.
+const CONNECTOR_IDLE: Duration = Duration::from_secs(30);
+
+/// The client of an endpoint, with its channel manager for the requests the client can't
+/// make itself and the connector its channel is made with.
+#[derive(Clone)]
+pub struct Transport {
+    pub client: Client,
+    pub channels: ChannelManager,
+    pub connector: Option<Connector>,
+}
+
+impl Transport {
+    /// Make the channel to `url` with the connector, replacing the current one.
+    pub fn connect(&self, url: &str, runtime: &Handle) -> Result<(), String> {
+        if let Some(connector) = &self.connector {
+            // The channel spawns its worker on the runtime
+            let _guard = runtime.enter();
+            self.channels
+                .reset_with_connector(url, connector.clone())
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// A configured endpoint with its own client, so requests can be routed to it explicitly.
+pub struct Endpoint {
+    pub url: String,
+    transport: RwLock<Transport>,
+    last_used: Mutex<Instant>,
+    /// Handles by database name with the credentials they were made with, dropped with
+    /// the transport.
+    databases: Mutex<HashMap<String, (Option<AuthScheme>, Database)>>,
+    runtime: Handle,
+    state: Mutex<EndpointState>,
+}
+
+impl Endpoint {
+    pub fn new(url: String, transport: Transport, runtime: Handle) -> Self {
+        Endpoint {
+            url,
+            transport: RwLock::new(transport),
+            last_used: Mutex::new(Instant::now()),
+            databases: Mutex::new(HashMap::new()),
+            runtime,
+            state: Mutex::new(EndpointState::default()),
+        }
+    }
+
+    fn transport(&self) -> Transport {
+        let transport = self.transport.read().unwrap().clone();
+        self.touch(&transport);
+        transport
+    }
+
+    /// Make the channel again when it may have been recycled, the transport is to be used.
+    fn touch(&self, transport: &Transport) {
+        let mut last_used = self.last_used.lock().unwrap();
+        if transport.connector.is_some() && last_used.elapsed() >= CONNECTOR_IDLE {
+            if let Err(e) = transport.connect(&self.url, &self.runtime) {
+                log::warn!("failed to reconnect {}: {e}", self.url);
+            }
+        }
+        *last_used = Instant::now();
+    }
+
+    pub fn client(&self) -> Client {
+        self.transport().client
+    }
+
+    /// A `Database` named `dbname` on the endpoint, made once per name and credentials.
+    pub fn database(&self, dbname: &str, auth: Option<AuthScheme>) -> Database {
+        let transport = self.transport.read().unwrap();
+        self.touch(&transport);
+        let mut databases = self.databases.lock().unwrap();
+        match databases.get(dbname) {
+            Some((cached_auth, db)) if *cached_auth == auth => db.clone(),
+            _ => {
+                let mut db =
+                    Database::new_with_dbname(dbname.to_string(), transport.client.clone());
+                if let Some(auth) = auth.clone() {
+                    db.set_auth(auth);
+                }
+                databases.insert(dbname.to_string(), (auth, db.clone()));
+                db
+            }
+        }
+    }
+
+    /// A channel to the endpoint, shared with the client.
+    pub fn channel(&self) -> Result<tonic::transport::Channel, String> {
+        self.transport()
+            .channels
+            .get(&self.url)
+            .map_err(|e| e.to_string())
+    }
+
+    fn is_available(&self) -> bool {
+        self.state.lock().unwrap().health != Health::Down
+    }
+
+    fn latency(&self) -> Option<Duration> {
+        self.state.lock().unwrap().latency
+    }
+
+    fn record(&self, result: Result<Duration, String>) {
+        let mut state = self.state.lock().unwrap();
+        state.last_check = Some(SystemTime::now());
+        match result {
+            Ok(latency) => {
+                state.health = Health::Up;
+                state.latency = Some(latency);
+                state.last_error = None;
+            }
+            Err(e) => {
+                state.health = Health::Down;
+                state.latency = None;
+                state.last_error = Some(e);
+            }
+        }
+    }
+
+    fn status(&self) -> EndpointStatus {
+        let connector = self.transport.read().unwrap().connector.clone();
+        let state = self.state.lock().unwrap();
+        EndpointStatus {
+            cipher_suite: connector.as_ref().and_then(Connector::cipher_suite),
+            fips: connector.as_ref().is_some_and(Connector::fips),
+            endpoint: self.url.clone(),
+            health: state.health,
+            latency: state.latency,
+            last_error: state.last_error.clone(),
+            last_check: state.last_check,
+        }
+    }
+}
+
+pub struct Endpoints {
+    endpoints: Vec<Endpoint>,
+    balance: Balance,
+    next: AtomicUsize,
+}
+
+impl Endpoints {
+    /// `endpoints` must not be empty.
+    pub fn new(endpoints: Vec<Endpoint>, balance: Balance) -> Self {
+        Endpoints {
+            endpoints,
+            balance,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pick an endpoint according to the balancing policy, skipping the ones found down.
+    /// Falls back to all endpoints when none is healthy.
+    pub fn pick(&self) -> &Endpoint {
+        let any_healthy = self.endpoints.iter().any(Endpoint::is_available);
+        let candidates = || {
+            self.endpoints
+                .iter()
+                .filter(move |e| !any_healthy || e.is_available())
+        };
+
+        match self.balance {
+            Balance::RoundRobin => {
+                let next = self.next.fetch_add(1, Ordering::Relaxed);
+                let count = candidates().count().max(1);
+                // The health may change between the passes, then any endpoint will do.
+                candidates()
+                    .nth(next % count)
+                    .unwrap_or(&self.endpoints[next % self.endpoints.len()])
+            }
+            Balance::LeastLatency => candidates()
+                .min_by_key(|e| e.latency().unwrap_or(Duration::MAX))
+                .unwrap_or(&self.endpoints[0]),
+        }
+    }
+
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        self.endpoints.iter().map(Endpoint::status).collect()
+    }
+
+    /// Replace the client of every endpoint, e.g. to pick up new certificates.
+    /// Either all clients are replaced or none is.
+    pub fn rebuild<F>(&self, build: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Result<Transport, String>,
+    {
+        let transports = self
+            .endpoints
+            .iter()
+            .map(|e| build(&e.url))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (endpoint, transport) in self.endpoints.iter().zip(transports) {
+            *endpoint.transport.write().unwrap() = transport;
+            *endpoint.last_used.lock().unwrap() = Instant::now();
+            endpoint.databases.lock().unwrap().clear();
+        }
+        Ok(())
+    }
+}
+
+pub struct EndpointStatus {
+    endpoint: String,
+    cipher_suite: Option<String>,
+    fips: bool,
+    health: Health,
+    latency: Option<Duration>,
+    last_error: Option<String>,
+    last_check: Option<SystemTime>,
+}
+
+impl Encoder for EndpointStatus {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let undefined = rustler::types::atom::undefined().encode(env);
+        let health = match self.health {
+            Health::Unknown => atoms::unknown(),
+            Health::Up => atoms::up(),
+            Health::Down => atoms::down(),
+        };
+        let latency = self
+            .latency
+            .map_or(undefined, |d| (d.as_micros() as u64).encode(env));
+        let last_error = self
+            .last_error
+            .as_ref()
+            .map_or(undefined, |e| e.encode(env));
+        let cipher_suite = self
+            .cipher_suite
+            .as_ref()
+            .map_or(undefined, |s| s.encode(env));
+        let last_check = self.last_check.map_or(undefined, |t| {
+            (t.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64).encode(env)
+        });
+
+        Term::map_from_pairs(
+            env,
+            &[
+                (atoms::endpoint().encode(env), self.endpoint.encode(env)),
+                (atoms::status().encode(env), health.encode(env)),
+                (atoms::latency_us().encode(env), latency),
+                (atoms::last_error().encode(env), last_error),
+                (atoms::last_check().encode(env), last_check),
+                (atoms::cipher_suite().encode(env), cipher_suite),
+                (atoms::fips().encode(env), self.fips.encode(env)),
+            ],
+        )
+        .expect("unique keys")
+    }
+}
+
+/// Minimal round-trip to the server.
+pub async fn probe(db: &Database) -> Result<(), String> {
+    let mut stream = db.query("SELECT 1").await.map_err(|e| e.to_string())?;
+    while let Some(batch) = stream.next().await {
+        batch.map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Round-trip that also reports the server version.
+pub async fn server_version(db: &Database) -> Result<Option<String>, String> {
+    let mut stream = db
+        .query("SELECT version()")
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut version = None;
+    while let Some(batch) = stream.next().await {
+        let batch = batch.map_err(|e| e.to_string())?;
+        if version.is_none() && batch.num_columns() > 0 && batch.num_rows() > 0 {
+            version = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<arrow::array::StringArray>()
+                .map(|versions| versions.value(0).to_string());
+        }
+    }
+    Ok(version)
+}
+
+/// Periodically probe every endpoint on the connection runtime.
+///
+/// The task stops once the endpoints are dropped with their connection.
+pub fn spawn_health_check(
+    endpoints: &Arc<Endpoints>,
+    runtime: &Runtime,
+    dbname: String,
+    auth: SharedAuth,
+    interval: Duration,
+    timeout: Duration,
+) {
+    let weak = Arc::downgrade(endpoints);
+    runtime.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let Some(endpoints) = weak.upgrade() else {
+                break;
+            };
+
+            let checks = endpoints.endpoints.iter().map(|endpoint| {
+                let db = endpoint.database(&dbname, auth.read().unwrap().clone());
+                async move {
+                    let started = Instant::now();
+                    let result = match tokio::time::timeout(timeout, probe(&db)).await {
+                        Ok(Ok(())) => Ok(started.elapsed()),
+                        Ok(Err(e)) => Err(e),
+                        Err(_) => Err("health check timed out".to_string()),
+                    };
+                    endpoint.record(result);
+                }
+            });
+            futures::future::join_all(checks).await;
+        }
+    });
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use arrow::array::{Array, StringArray};
+use arrow::datatypes::DataType;
+use greptimedb_ingester::database::Database;
+use rustler::{Encoder, Env, Term};
+
+use crate::atoms;
+
+/// Column names and rows of a query with every value cast to a string, `None` for nulls.
+async fn query_strings(
+    db: &Database,
+    sql: &str,
+) -> Result<(Vec<String>, Vec<Vec<Option<String>>>), String> {
+    let mut stream = db.query(sql).await.map_err(|e| e.to_string())?;
+    let mut names = Vec::new();
+    let mut rows = Vec::new();
+    while let Some(batch) = futures::StreamExt::next(&mut stream).await {
+        let batch = batch.map_err(|e| e.to_string())?;
+        if names.is_empty() {
+            names = batch
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| field.name().clone())
+                .collect();
+        }
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|column| {
+                let column =
+                    arrow::compute::cast(column, &DataType::Utf8).map_err(|e| e.to_string())?;
+                column
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .cloned()
+                    .ok_or_else(|| "failed to cast column to strings".to_string())
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        for i in 0..batch.num_rows() {
+            let row = columns
+                .iter()
+                .map(|column| column.is_valid(i).then(|| column.value(i).to_string()))
+                .collect();
+            rows.push(row);
+        }
+    }
+    Ok((names, rows))
+}
+
+/// The first column of each row.
+async fn query_names(db: &Database, sql: &str) -> Result<Vec<String>, String> {
+    let (_, rows) = query_strings(db, sql).await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| row.into_iter().next().flatten())
+        .collect())
+}
+
+/// A SQL string literal.
+fn literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// A quoted SQL identifier, matched case-sensitively.
+pub fn identifier(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+pub async fn list_databases(db: &Database) -> Result<Vec<String>, String> {
+    query_names(
+        db,
+        "SELECT schema_name FROM information_schema.schemata ORDER BY schema_name",
+    )
+    .await
+}
+
+pub async fn list_tables(db: &Database, dbname: &str) -> Result<Vec<String>, String> {
+    let sql = format!(
+        "SELECT table_name FROM information_schema.tables WHERE table_schema = {} \
+         ORDER BY table_name",
+        literal(dbname)
+    );
+    query_names(db, &sql).await
+}
+
+pub async fn table_exists(db: &Database, dbname: &str, table: &str) -> Result<bool, String> {
+    let sql = format!(
+        "SELECT table_name FROM information_schema.tables \
+         WHERE table_schema = {} AND table_name = {}",
+        literal(dbname),
+        literal(table)
+    );
+    Ok(!query_names(db, &sql).await?.is_empty())
+}
+
+/// A column of a table, as `describe_table` reports it.
+pub struct Column {
+    name: String,
+    data_type: String,
+    semantic_type: String,
+    nullable: bool,
+    default: Option<String>,
+    /// Constraint names from `key_column_usage`, such as `TIME INDEX` or `INVERTED INDEX`.
+    indexes: Vec<String>,
+}
+
+/// The columns of `table`, in the order of the table.
+pub async fn describe_table(
+    db: &Database,
+    dbname: &str,
+    table: &str,
+) -> Result<Vec<Column>, String> {
+    // Types are named as in `fetch_table_schema`, `information_schema` has them in lowercase
+    let sql = format!(
+        "DESCRIBE TABLE {}.{}",
+        identifier(dbname),
+        identifier(table)
+    );
+    let (names, rows) = query_strings(db, &sql).await?;
+    let position = |name: &str| {
+        names
+            .iter()
+            .position(|n| n.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("DESCRIBE result has no `{name}` column"))
+    };
+    let (name_at, type_at, nullable_at, default_at, semantic_at) = (
+        position("Column")?,
+        position("Type")?,
+        position("Null")?,
+        position("Default")?,
+        position("Semantic Type")?,
+    );
+
+    let sql = format!(
+        "SELECT column_name, constraint_name FROM information_schema.key_column_usage \
+         WHERE table_schema = {} AND table_name = {}",
+        literal(dbname),
+        literal(table)
+    );
+    let mut indexes: HashMap<String, Vec<String>> = HashMap::new();
+    for row in query_strings(db, &sql).await?.1 {
+        if let [Some(column), Some(constraint)] = &row[..] {
+            indexes
+                .entry(column.clone())
+                .or_default()
+                .push(constraint.clone());
+        }
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|mut row| {
+            let name = row[name_at].take().unwrap_or_default();
+            Column {
+                indexes: indexes.remove(&name).unwrap_or_default(),
+                name,
+                data_type: row[type_at].take().unwrap_or_default(),
+                semantic_type: row[semantic_at].take().unwrap_or_default(),
+                nullable: row[nullable_at]
+                    .as_deref()
+                    .is_some_and(|n| n.eq_ignore_ascii_case("yes")),
+                default: row[default_at].take().filter(|d| !d.is_empty()),
+            }
+        })
+        .collect())
+}
+
+impl Encoder for Column {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let semantic_type = match self.semantic_type.as_str() {
+            "TAG" => atoms::tag().encode(env),
+            "FIELD" => atoms::field().encode(env),
+            "TIMESTAMP" => atoms::timestamp().encode(env),
+            other => other.encode(env),
+        };
+        let indexes: Vec<Term> = self
+            .indexes
+            .iter()
+            .map(|index| match index.as_str() {
+                "TIME INDEX" => atoms::time_index().encode(env),
+                "PRIMARY" => atoms::primary_key().encode(env),
+                "INVERTED INDEX" => atoms::inverted().encode(env),
+                "FULLTEXT INDEX" => atoms::fulltext().encode(env),
+                "SKIPPING INDEX" => atoms::skipping().encode(env),
+                other => other.encode(env),
+            })
+            .collect();
+        let default = match &self.default {
+            Some(default) => default.encode(env),
+            None => rustler::types::atom::undefined().encode(env),
+        };
+        Term::map_from_pairs(
+            env,
+            &[
+                (atoms::name().encode(env), self.name.encode(env)),
+                (atoms::data_type().encode(env), self.data_type.encode(env)),
+                (atoms::semantic_type().encode(env), semantic_type),
+                (atoms::nullable().encode(env), self.nullable.encode(env)),
+                (atoms::default().encode(env), default),
+                (atoms::indexes().encode(env), indexes.encode(env)),
+            ],
+        )
+        .expect("unique keys")
+    }
+}
@@ -1,9 +1,12 @@
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use greptime_proto::v1::auth_header::AuthScheme;
-use greptime_proto::v1::Basic;
+use greptime_proto::v1::{AuthHeader, Basic, RequestHeader, Token};
+use greptimedb_ingester::api::v1::{ColumnSchema, Row, SemanticType};
 use greptimedb_ingester::client::Client;
 use greptimedb_ingester::database::Database;
 use greptimedb_ingester::{
@@ -12,21 +15,105 @@ use greptimedb_ingester::{
 use rustler::{Atom, Encoder, Env, NifResult, ResourceArc, Term};
 use tokio::runtime::Runtime;
 
-static FIPS_ENABLED: AtomicBool = AtomicBool::new(false);
-
 pub mod atoms;
+mod buffer;
+mod compression;
+mod connector;
+mod dedup;
+mod fips;
+mod health;
+mod introspect;
+mod limits;
+mod logger;
+mod metrics;
+mod proxy;
+mod retry;
+mod spool;
+mod tls;
+mod trace;
 mod types;
 mod util;
 
+/// Credentials shared by all requests of a connection, replaced in place by `update_auth`.
+pub type SharedAuth = Arc<RwLock<Option<AuthScheme>>>;
+
 pub struct GreptimeResource {
-    pub db: Database,
-    pub client: Client,
-    pub auth: Option<AuthScheme>,
+    pub endpoints: Arc<health::Endpoints>,
+    pub dbname: String,
+    pub auth: SharedAuth,
     pub ts_column: Option<String>,
     pub ttl: Option<String>,
+    pub convert_opts: util::ConvertOptions,
+    pub retry: Option<retry::RetryPolicy>,
+    pub tls: Mutex<Option<tls::Tls>>,
+    pub channel: Option<ChannelConfig>,
+    pub proxy: Option<Arc<proxy::ProxyConfig>>,
+    pub compression: compression::Compression,
+    /// Shared with the streams, which report whether they are open.
+    pub metrics: Arc<metrics::ConnectionMetrics>,
+    /// Exports the spans of traced calls, when `otlp_endpoint` is set.
+    pub tracing: Option<Arc<trace::Tracing>>,
+    /// Keeps the inserts the server could not take, when `spool` is set.
+    pub spool: Option<Arc<spool::Spool>>,
+    /// Idempotency keys of the inserts, shared by the connections to the same database.
+    pub dedup: Arc<dedup::Dedup>,
+    /// Bounds the inserts, stream writes and buffered rows in flight on the connection.
+    pub limiter: Arc<limits::Limiter>,
+    /// Limits of each stream started on the connection.
+    pub stream_limits: limits::Limits,
     pub runtime: Arc<Runtime>, // Per-connection runtime
 }
 
+impl GreptimeResource {
+    /// A `Database` on the endpoint picked by the balancing policy.
+    fn database(&self) -> Database {
+        self.database_in(&self.dbname)
+    }
+
+    /// As `database`, for another database of the same server.
+    fn database_in(&self, dbname: &str) -> Database {
+        self.database_on(self.endpoints.pick(), dbname)
+    }
+
+    fn database_on(&self, endpoint: &health::Endpoint, dbname: &str) -> Database {
+        endpoint.database(dbname, self.current_auth())
+    }
+
+    fn current_auth(&self) -> Option<AuthScheme> {
+        self.auth.read().unwrap().clone()
+    }
+
+    /// [`request_header`] with the current credentials.
+    fn request_header(&self, dbname: &str) -> RequestHeader {
+        request_header(dbname, self.current_auth())
+    }
+
+    /// The schema of `table` in the database `db` is on, named `dbname`.
+    async fn fetch_table_schema(
+        &self,
+        db: &Database,
+        dbname: &str,
+        table: &str,
+    ) -> Result<TableSchema, String> {
+        let started = Instant::now();
+        let result = fetch_table_schema(db, dbname, table).await;
+        self.metrics.schema_fetches.fetch_add(1, Ordering::Relaxed);
+        self.metrics.schema_fetch_latency.observe(started.elapsed());
+        result
+    }
+}
+
+/// The header a `Database` would send, for requests made without one.
+fn request_header(dbname: &str, auth: Option<AuthScheme>) -> RequestHeader {
+    RequestHeader {
+        dbname: dbname.to_string(),
+        authorization: auth.map(|auth_scheme| AuthHeader {
+            auth_scheme: Some(auth_scheme),
+        }),
+        ..Default::default()
+    }
+}
+
 // Wrapper to force Send/Sync on BulkStreamWriter
 pub struct SendableBulkStreamWriter(pub BulkStreamWriter);
 unsafe impl Send for SendableBulkStreamWriter {}
@@ -34,30 +121,120 @@ unsafe impl Sync for SendableBulkStreamWriter {}
 
 pub struct StreamWriterResource {
     pub writer: tokio::sync::Mutex<Option<SendableBulkStreamWriter>>,
+    /// Where the stream was opened, to open it again after a failed write.
+    pub client: Client,
+    /// The channel of `client`, for writes that carry a trace context.
+    pub channel: tonic::transport::Channel,
+    pub dbname: String,
+    pub table: String,
+    pub auth: SharedAuth,
     pub schema: TableSchema,
+    pub convert_opts: util::ConvertOptions,
+    pub retry: Option<retry::RetryPolicy>,
+    pub metrics: metrics::StreamMetrics,
+    pub tracing: Option<Arc<trace::Tracing>>,
+    pub connection_metrics: Arc<metrics::ConnectionMetrics>,
+    pub limiter: Arc<limits::Limiter>,
+    pub connection_limiter: Arc<limits::Limiter>,
     pub runtime: Arc<Runtime>, // Need runtime for async operations
 }
 
+impl Drop for StreamWriterResource {
+    fn drop(&mut self) {
+        // Not closed with `stream_close`
+        if self.writer.get_mut().is_some() {
+            self.connection_metrics
+                .streams_open
+                .fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A buffer on a connection, kept open by its writer.
+pub struct BufferedWriterResource {
+    pub buffer: Arc<buffer::Buffer>,
+    pub connection: ResourceArc<GreptimeResource>,
+}
+
 #[allow(non_local_definitions)]
 fn load(env: Env, _info: Term) -> bool {
-    FIPS_ENABLED.store(check_fips_enabled(), Ordering::Relaxed);
+    fips::detect();
+    logger::init();
     _ = rustler::resource!(GreptimeResource, env);
     _ = rustler::resource!(StreamWriterResource, env);
+    _ = rustler::resource!(BufferedWriterResource, env);
     true
 }
 
-fn check_fips_enabled() -> bool {
-    std::fs::read_to_string("/proc/sys/crypto/fips_enabled")
-        .map(|s| s.trim() == "1")
-        .unwrap_or(false)
+/// Detects the host state again, `fips_mode => auto` connections made afterwards follow it.
+#[rustler::nif]
+fn fips_status() -> bool {
+    fips::detect()
 }
 
+fn decode_log_level(term: Term) -> Result<log::LevelFilter, String> {
+    if let Ok(atom) = term.decode::<Atom>() {
+        for (name, level) in [
+            (atoms::none(), log::LevelFilter::Off),
+            (atoms::error(), log::LevelFilter::Error),
+            (atoms::warning(), log::LevelFilter::Warn),
+            (atoms::info(), log::LevelFilter::Info),
+            (atoms::debug(), log::LevelFilter::Debug),
+            (atoms::trace(), log::LevelFilter::Trace),
+        ] {
+            if atom == name {
+                return Ok(level);
+            }
+        }
+    }
+
+    Err("invalid log level".to_string())
+}
+
+fn decode_log_handler(term: Term) -> Result<Option<rustler::LocalPid>, String> {
+    if let Ok(pid) = term.decode::<rustler::LocalPid>() {
+        return Ok(Some(pid));
+    }
+    if let Ok(atom) = term.decode::<Atom>() {
+        if atom == rustler::types::atom::undefined() {
+            return Ok(None);
+        }
+    }
+
+    Err("invalid log handler".to_string())
+}
+
+/// Forward the records of the ingester and its dependencies to `handler`, or stop
+/// with `undefined`. At most `max_per_second` are sent, the others are counted and reported.
 #[rustler::nif]
-fn fips_status() -> bool {
-    FIPS_ENABLED.load(Ordering::Relaxed)
+fn set_log_handler<'a>(env: Env<'a>, handler: Term<'a>, opts: Term<'a>) -> NifResult<Term<'a>> {
+    let handler = match decode_log_handler(handler) {
+        Ok(handler) => handler,
+        Err(err) => return Ok((atoms::error(), err).encode(env)),
+    };
+    let max_per_second = match opts.map_get(atoms::max_per_second().to_term(env)) {
+        Ok(term) => match term.decode::<u32>() {
+            Ok(n) if n > 0 => n,
+            _ => return Ok((atoms::error(), "invalid max_per_second").encode(env)),
+        },
+        Err(_) => logger::DEFAULT_MAX_PER_SECOND,
+    };
+    logger::set_handler(handler, max_per_second);
+    Ok(atoms::ok().encode(env))
 }
 
-use greptimedb_ingester::channel_manager::{ClientTlsOption, TlsVerify};
+#[rustler::nif]
+fn set_log_level<'a>(env: Env<'a>, level: Term<'a>) -> NifResult<Term<'a>> {
+    match decode_log_level(level) {
+        Ok(level) => {
+            log::set_max_level(level);
+            Ok(atoms::ok().encode(env))
+        }
+        Err(err) => Ok((atoms::error(), err).encode(env)),
+    }
+}
+
+use greptimedb_ingester::channel_manager::{ChannelConfig, ChannelManager, TlsVerify};
 
 fn decode_tls_verify(verify_term: Term) -> Result<TlsVerify, String> {
     if let Ok(verify_atom) = verify_term.decode::<Atom>() {
@@ -86,6 +263,594 @@ fn decode_tls_verify(verify_term: Term) -> Result<TlsVerify, String> {
     Err("invalid verify option".to_string())
 }
 
+/// A path or a PEM binary. `undefined`, `nil` and `<<>>` mean not set.
+fn decode_pem_source(term: Term) -> Result<Option<tls::PemSource>, String> {
+    if let Ok(atom) = term.decode::<Atom>() {
+        if atom == rustler::types::atom::undefined() || atom == rustler::types::atom::nil() {
+            return Ok(None);
+        }
+    }
+    match term.decode::<rustler::Binary>() {
+        Ok(bin) if bin.is_empty() => Ok(None),
+        Ok(bin) => tls::PemSource::from_bytes(bin.as_slice()).map(Some),
+        Err(_) => Err("expected a path or PEM binary".to_string()),
+    }
+}
+
+/// Decode and validate the TLS keys of `opts`. Keys that are absent keep their value in `base`.
+fn decode_tls_config(
+    opts: Term,
+    base: Option<&tls::TlsConfig>,
+) -> Result<tls::TlsConfig, tls::ConfigError> {
+    let env = opts.get_env();
+    let get = |key: Atom| opts.map_get(key.to_term(env)).ok();
+    let pem = |key: Atom| -> Result<Option<Option<tls::PemSource>>, tls::ConfigError> {
+        get(key)
+            .map(decode_pem_source)
+            .transpose()
+            .map_err(|e| tls::ConfigError::new(key, e))
+    };
+
+    let mut config = base.cloned().unwrap_or(tls::TlsConfig {
+        verify: TlsVerify::VerifyPeer,
+        ca_cert: None,
+        client_cert: None,
+        client_key: None,
+        cipher_suites: Vec::new(),
+    });
+    if let Some(t) = get(atoms::verify()) {
+        config.verify =
+            decode_tls_verify(t).map_err(|e| tls::ConfigError::new(atoms::verify(), e))?;
+    }
+    if let Some(ca_cert) = pem(atoms::ca_cert())? {
+        config.ca_cert = ca_cert;
+    }
+    if let Some(client_cert) = pem(atoms::client_cert())? {
+        config.client_cert = client_cert;
+    }
+    if let Some(client_key) = pem(atoms::client_key())? {
+        config.client_key = client_key;
+    }
+    if let Some(t) = get(atoms::cipher_suites()) {
+        config.cipher_suites = t.decode().map_err(|_| {
+            tls::ConfigError::new(atoms::cipher_suites(), "expected a list of binaries")
+        })?;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+/// Decode `#{url := Url, username => User, password => Password, no_proxy => [Host]}`.
+fn decode_proxy(term: Term) -> Result<proxy::ProxyConfig, String> {
+    let env = term.get_env();
+    let get = |key: Atom| term.map_get(key.to_term(env)).ok();
+    let invalid = |name: &str| format!("invalid proxy option `{name}`");
+
+    let url: String = get(atoms::url())
+        .ok_or_else(|| "proxy url is required".to_string())?
+        .decode()
+        .map_err(|_| invalid("url"))?;
+    let (kind, addr) = proxy::ProxyConfig::parse_url(&url)?;
+
+    let username: Option<String> = get(atoms::username())
+        .map(|t| t.decode())
+        .transpose()
+        .map_err(|_| invalid("username"))?;
+    let password: Option<String> = get(atoms::password())
+        .map(|t| t.decode())
+        .transpose()
+        .map_err(|_| invalid("password"))?;
+    let auth = match (username, password) {
+        (Some(username), password) => Some((username, password.unwrap_or_default())),
+        (None, Some(_)) => return Err(invalid("password")),
+        (None, None) => None,
+    };
+
+    let no_proxy: Vec<String> = get(atoms::no_proxy())
+        .map(|t| t.decode())
+        .transpose()
+        .map_err(|_| invalid("no_proxy"))?
+        .unwrap_or_default();
+
+    Ok(proxy::ProxyConfig {
+        kind,
+        addr,
+        auth,
+        no_proxy,
+    })
+}
+
+fn decode_fips_mode(term: Term) -> Result<fips::FipsMode, String> {
+    if let Ok(atom) = term.decode::<Atom>() {
+        if atom == atoms::auto() {
+            return Ok(fips::FipsMode::Auto);
+        }
+        if atom == atoms::required() {
+            return Ok(fips::FipsMode::Required);
+        }
+        if atom == atoms::off() {
+            return Ok(fips::FipsMode::Off);
+        }
+    }
+
+    Err("invalid fips_mode option".to_string())
+}
+
+fn decode_compression(term: Term) -> Result<compression::Compression, String> {
+    if let Ok(atom) = term.decode::<Atom>() {
+        if atom == atoms::none() {
+            return Ok(compression::Compression::None);
+        }
+        if atom == atoms::gzip() {
+            return Ok(compression::Compression::Gzip);
+        }
+        if atom == atoms::zstd() {
+            return Ok(compression::Compression::Zstd);
+        }
+    }
+
+    Err("invalid compression option".to_string())
+}
+
+/// Decode the channel tuning options, durations in milliseconds and sizes in bytes.
+/// Returns `None` when none is given, keeping the channel manager defaults.
+fn decode_channel_config(
+    opts: Term,
+    compression: compression::Compression,
+) -> Result<Option<ChannelConfig>, String> {
+    let env = opts.get_env();
+    let get = |key: Atom| opts.map_get(key.to_term(env)).ok();
+    let invalid = |name: &str| format!("invalid {name} option");
+    let millis = |key: Atom, name: &str| -> Result<Option<Duration>, String> {
+        get(key)
+            .map(|t| t.decode::<u64>().map(Duration::from_millis))
+            .transpose()
+            .map_err(|_| invalid(name))
+    };
+    let size = |key: Atom, name: &str| -> Result<Option<u32>, String> {
+        get(key)
+            .map(|t| t.decode::<u32>())
+            .transpose()
+            .map_err(|_| invalid(name))
+    };
+
+    let mut config = ChannelConfig::default();
+    let mut tuned = false;
+    if let Some(d) = millis(atoms::connect_timeout(), "connect_timeout")? {
+        config = config.connect_timeout(d);
+        tuned = true;
+    }
+    if let Some(d) = millis(atoms::request_timeout(), "request_timeout")? {
+        config = config.timeout(d);
+        tuned = true;
+    }
+    if let Some(d) = millis(atoms::keepalive_interval(), "keepalive_interval")? {
+        config = config.http2_keep_alive_interval(d);
+        tuned = true;
+    }
+    if let Some(d) = millis(atoms::keepalive_timeout(), "keepalive_timeout")? {
+        config = config.http2_keep_alive_timeout(d);
+        tuned = true;
+    }
+    if let Some(t) = get(atoms::keepalive_while_idle()) {
+        let while_idle = t.decode().map_err(|_| invalid("keepalive_while_idle"))?;
+        config = config.http2_keep_alive_while_idle(while_idle);
+        tuned = true;
+    }
+    if let Some(d) = millis(atoms::tcp_keepalive(), "tcp_keepalive")? {
+        config = config.tcp_keepalive(d);
+        tuned = true;
+    }
+    if let Some(n) = size(atoms::max_send_message_size(), "max_send_message_size")? {
+        config = config.max_send_message_size(n as usize);
+        tuned = true;
+    }
+    if let Some(n) = size(atoms::max_recv_message_size(), "max_recv_message_size")? {
+        config = config.max_recv_message_size(n as usize);
+        tuned = true;
+    }
+    if let Some(n) = size(
+        atoms::initial_stream_window_size(),
+        "initial_stream_window_size",
+    )? {
+        config = config.initial_stream_window_size(n);
+        tuned = true;
+    }
+    if let Some(n) = size(
+        atoms::initial_connection_window_size(),
+        "initial_connection_window_size",
+    )? {
+        config = config.initial_connection_window_size(n);
+        tuned = true;
+    }
+    if let Some(encoding) = compression.encoding() {
+        config = config
+            .send_compression(encoding)
+            .accept_compression(encoding);
+        tuned = true;
+    }
+    Ok(tuned.then_some(config))
+}
+
+/// The client of an endpoint. With TLS or a proxy, its channel is made with a connector
+/// going through the proxy and doing the handshake with the connection's settings.
+fn build_client(
+    url: &str,
+    channel: Option<&ChannelConfig>,
+    tls: Option<&Arc<rustls::ClientConfig>>,
+    proxy: Option<&Arc<proxy::ProxyConfig>>,
+    runtime: &Runtime,
+) -> Result<health::Transport, String> {
+    let channels = ChannelManager::with_config(channel.cloned().unwrap_or_default());
+    let client = Client::with_manager_and_urls(channels.clone(), vec![url.to_string()]);
+    let connector = (tls.is_some() || proxy.is_some())
+        .then(|| connector::Connector::new(tls.cloned(), proxy.cloned()));
+    let transport = health::Transport {
+        client,
+        channels,
+        connector,
+    };
+    transport.connect(url, runtime.handle())?;
+    Ok(transport)
+}
+
+fn decode_default_timestamp(term: Term) -> Result<util::DefaultTimestamp, String> {
+    if let Ok(atom) = term.decode::<Atom>() {
+        if atom == atoms::now() {
+            return Ok(util::DefaultTimestamp::Now);
+        }
+        if atom == atoms::error() {
+            return Ok(util::DefaultTimestamp::Error);
+        }
+    }
+
+    if let Ok((tag, name)) = term.decode::<(Atom, String)>() {
+        if tag == atoms::column() {
+            return Ok(util::DefaultTimestamp::Column(name));
+        }
+    }
+
+    Err("invalid default_timestamp option".to_string())
+}
+
+fn decode_coercion(term: Term) -> Result<types::Coercion, String> {
+    if let Ok(atom) = term.decode::<Atom>() {
+        if atom == atoms::strict() {
+            return Ok(types::Coercion::Strict);
+        }
+        if atom == atoms::widen() {
+            return Ok(types::Coercion::Widen);
+        }
+        if atom == atoms::stringify() {
+            return Ok(types::Coercion::Stringify);
+        }
+        if atom == atoms::null_on_error() {
+            return Ok(types::Coercion::NullOnError);
+        }
+    }
+
+    Err("invalid coercion option".to_string())
+}
+
+fn decode_column_data_type(term: Term) -> Result<ColumnDataType, String> {
+    let name = term
+        .atom_to_string()
+        .map_err(|_| "invalid column type".to_string())?;
+    let dtype = match name.as_str() {
+        "boolean" => ColumnDataType::Boolean,
+        "int8" => ColumnDataType::Int8,
+        "int16" => ColumnDataType::Int16,
+        "int32" => ColumnDataType::Int32,
+        "int64" => ColumnDataType::Int64,
+        "uint8" => ColumnDataType::Uint8,
+        "uint16" => ColumnDataType::Uint16,
+        "uint32" => ColumnDataType::Uint32,
+        "uint64" => ColumnDataType::Uint64,
+        "float32" => ColumnDataType::Float32,
+        "float64" => ColumnDataType::Float64,
+        "string" => ColumnDataType::String,
+        "binary" => ColumnDataType::Binary,
+        "json" => ColumnDataType::Json,
+        "date" => ColumnDataType::Date,
+        "datetime" => ColumnDataType::Datetime,
+        "timestamp_second" => ColumnDataType::TimestampSecond,
+        "timestamp_millisecond" => ColumnDataType::TimestampMillisecond,
+        "timestamp_microsecond" => ColumnDataType::TimestampMicrosecond,
+        "timestamp_nanosecond" => ColumnDataType::TimestampNanosecond,
+        _ => return Err(format!("invalid column type `{name}`")),
+    };
+    Ok(dtype)
+}
+
+/// Decode `#{Column => Type | {Type, tag | field}}`.
+fn decode_schema_hints(term: Term) -> Result<util::SchemaHints, String> {
+    let iter = term
+        .decode::<rustler::MapIterator>()
+        .map_err(|_| "invalid schema_hints option".to_string())?;
+
+    let mut hints = util::SchemaHints::new();
+    for (key, value) in iter {
+        let name: String = key
+            .decode()
+            .map_err(|_| "invalid schema_hints column name".to_string())?;
+        let hint = match value.decode::<(Term, Atom)>() {
+            Ok((dtype, semantic)) => {
+                let semantic = if semantic == atoms::tag() {
+                    SemanticType::Tag
+                } else if semantic == atoms::field() {
+                    SemanticType::Field
+                } else {
+                    return Err(format!("invalid semantic type for column `{name}`"));
+                };
+                util::ColumnHint {
+                    dtype: decode_column_data_type(dtype)?,
+                    semantic: Some(semantic),
+                }
+            }
+            Err(_) => util::ColumnHint {
+                dtype: decode_column_data_type(value)?,
+                semantic: None,
+            },
+        };
+        hints.insert(name, hint);
+    }
+    Ok(hints)
+}
+
+fn decode_balance(term: Term) -> Result<health::Balance, String> {
+    if let Ok(atom) = term.decode::<Atom>() {
+        if atom == atoms::round_robin() {
+            return Ok(health::Balance::RoundRobin);
+        }
+        if atom == atoms::least_latency() {
+            return Ok(health::Balance::LeastLatency);
+        }
+    }
+
+    Err("invalid balance option".to_string())
+}
+
+/// Decode `#{dir := Dir, max_bytes, segment_bytes, eviction, replay_interval}`,
+/// `replay_interval` in milliseconds.
+fn decode_spool_options(term: Term) -> Result<spool::SpoolOptions, String> {
+    let env = term.get_env();
+    let get = |key: Atom| term.map_get(key.to_term(env)).ok();
+    let invalid = |name: &str| format!("invalid spool option `{name}`");
+    let positive = |key: Atom, name: &str| -> Result<Option<u64>, String> {
+        match get(key).map(|t| t.decode::<u64>()) {
+            Some(Ok(n)) if n > 0 => Ok(Some(n)),
+            Some(_) => Err(invalid(name)),
+            None => Ok(None),
+        }
+    };
+
+    let dir: String = get(atoms::dir())
+        .ok_or_else(|| "spool dir is required".to_string())?
+        .decode()
+        .map_err(|_| invalid("dir"))?;
+    let eviction = match get(atoms::eviction()).map(|t| t.decode::<Atom>()) {
+        None => spool::Eviction::DropOldest,
+        Some(Ok(atom)) if atom == atoms::drop_oldest() => spool::Eviction::DropOldest,
+        Some(Ok(atom)) if atom == atoms::reject_new() => spool::Eviction::RejectNew,
+        Some(_) => return Err(invalid("eviction")),
+    };
+    Ok(spool::SpoolOptions {
+        dir: dir.into(),
+        max_bytes: positive(atoms::max_bytes(), "max_bytes")?.unwrap_or(spool::DEFAULT_MAX_BYTES),
+        segment_bytes: positive(atoms::segment_bytes(), "segment_bytes")?
+            .unwrap_or(spool::DEFAULT_SEGMENT_BYTES),
+        eviction,
+        replay_interval: positive(atoms::replay_interval(), "replay_interval")?
+            .map_or(spool::DEFAULT_REPLAY_INTERVAL, Duration::from_millis),
+    })
+}
+
+/// Decode `#{requests, bytes, timeout}`, `timeout` in milliseconds. `name` is the option
+/// being decoded, for the errors.
+fn decode_limits(term: Term, name: &str) -> Result<limits::Limits, String> {
+    if !term.is_map() {
+        return Err(format!("invalid {name} option"));
+    }
+    let env = term.get_env();
+    let get = |key: Atom| term.map_get(key.to_term(env)).ok();
+    let invalid = |key: &str| format!("invalid {name} option `{key}`");
+    let positive = |key: Atom, key_name: &str| -> Result<Option<u64>, String> {
+        match get(key).map(|t| t.decode::<u64>()) {
+            Some(Ok(n)) if n > 0 => Ok(Some(n)),
+            Some(_) => Err(invalid(key_name)),
+            None => Ok(None),
+        }
+    };
+
+    let timeout = get(atoms::timeout())
+        .map(|t| t.decode::<u64>().map(Duration::from_millis))
+        .transpose()
+        .map_err(|_| invalid("timeout"))?
+        .unwrap_or_default();
+    Ok(limits::Limits {
+        max_requests: positive(atoms::requests(), "requests")?,
+        max_bytes: positive(atoms::bytes(), "bytes")?,
+        timeout,
+    })
+}
+
+/// Decode credentials from `#{token := Token}` or `#{username := User, password := Password}`.
+/// Returns `None` when neither is given, an error for an incomplete or ambiguous map.
+fn decode_auth(term: Term) -> Result<Option<AuthScheme>, String> {
+    let env = term.get_env();
+    let get = |key: Atom, name: &str| -> Result<Option<String>, String> {
+        match term.map_get(key.to_term(env)) {
+            Ok(t) => t
+                .decode()
+                .map(Some)
+                .map_err(|_| format!("invalid {name} option")),
+            Err(_) => Ok(None),
+        }
+    };
+
+    match (
+        get(atoms::token(), "token")?,
+        get(atoms::username(), "username")?,
+        get(atoms::password(), "password")?,
+    ) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+            Err("token and username/password are mutually exclusive".to_string())
+        }
+        (Some(token), None, None) => Ok(Some(AuthScheme::Token(Token { token }))),
+        (None, Some(username), Some(password)) => {
+            Ok(Some(AuthScheme::Basic(Basic { username, password })))
+        }
+        (None, Some(_), None) => Err("username without password".to_string()),
+        (None, None, Some(_)) => Err("password without username".to_string()),
+        (None, None, None) => Ok(None),
+    }
+}
+
+/// The `traceparent` and `tracestate` per-call options.
+fn decode_trace_context(opts: Term) -> Result<Option<trace::TraceContext>, String> {
+    let env = opts.get_env();
+    let get = |key: Atom, name: &str| -> Result<Option<String>, String> {
+        match opts.map_get(key.to_term(env)) {
+            Ok(t)
+                if t.decode::<Atom>()
+                    .is_ok_and(|atom| atom == rustler::types::atom::undefined()) =>
+            {
+                Ok(None)
+            }
+            Ok(t) => t
+                .decode()
+                .map(Some)
+                .map_err(|_| format!("invalid {name} option")),
+            Err(_) => Ok(None),
+        }
+    };
+
+    match (
+        get(atoms::traceparent(), "traceparent")?,
+        get(atoms::tracestate(), "tracestate")?,
+    ) {
+        (Some(traceparent), tracestate) => {
+            trace::TraceContext::new(traceparent, tracestate).map(Some)
+        }
+        (None, Some(_)) => Err("tracestate requires traceparent".to_string()),
+        (None, None) => Ok(None),
+    }
+}
+
+fn decode_retry_code(term: Term) -> Result<retry::RetryCode, String> {
+    if let Ok(atom) = term.decode::<Atom>() {
+        let codes = [
+            (atoms::unavailable(), retry::RetryCode::Unavailable),
+            (
+                atoms::deadline_exceeded(),
+                retry::RetryCode::DeadlineExceeded,
+            ),
+            (
+                atoms::resource_exhausted(),
+                retry::RetryCode::ResourceExhausted,
+            ),
+            (atoms::aborted(), retry::RetryCode::Aborted),
+            (atoms::cancelled(), retry::RetryCode::Cancelled),
+            (atoms::internal(), retry::RetryCode::Internal),
+            (atoms::unknown(), retry::RetryCode::Unknown),
+        ];
+        if let Some((_, code)) = codes.into_iter().find(|(name, _)| *name == atom) {
+            return Ok(code);
+        }
+    }
+
+    Err("invalid retry_on status".to_string())
+}
+
+/// Decode `false | #{max_attempts, initial_backoff, max_backoff, jitter, retry_on, deadline}`,
+/// durations in milliseconds. Missing keys take the `RetryPolicy` defaults.
+fn decode_retry_policy(term: Term) -> Result<Option<retry::RetryPolicy>, String> {
+    if let Ok(false) = term.decode::<bool>() {
+        return Ok(None);
+    }
+    if !term.is_map() {
+        return Err("invalid retry option".to_string());
+    }
+
+    let env = term.get_env();
+    let get = |key: Atom| term.map_get(key.to_term(env)).ok();
+    let invalid = |key: &str| format!("invalid retry option `{key}`");
+    let millis = |key: Atom, name: &str| -> Result<Option<Duration>, String> {
+        get(key)
+            .map(|t| t.decode::<u64>().map(Duration::from_millis))
+            .transpose()
+            .map_err(|_| invalid(name))
+    };
+
+    let mut policy = retry::RetryPolicy::default();
+    if let Some(t) = get(atoms::max_attempts()) {
+        policy.max_attempts = match t.decode::<u32>() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(invalid("max_attempts")),
+        };
+    }
+    if let Some(d) = millis(atoms::initial_backoff(), "initial_backoff")? {
+        policy.initial_backoff = d;
+    }
+    if let Some(d) = millis(atoms::max_backoff(), "max_backoff")? {
+        policy.max_backoff = d;
+    }
+    if let Some(t) = get(atoms::jitter()) {
+        policy.jitter = match t.decode::<f64>() {
+            Ok(j) if (0.0..=1.0).contains(&j) => j,
+            _ => return Err(invalid("jitter")),
+        };
+    }
+    if let Some(t) = get(atoms::retry_on()) {
+        let codes: Vec<Term> = t.decode().map_err(|_| invalid("retry_on"))?;
+        policy.retry_on = codes
+            .into_iter()
+            .map(decode_retry_code)
+            .collect::<Result<_, _>>()?;
+    }
+    policy.deadline = match millis(atoms::deadline(), "deadline")? {
+        Some(d) if d.is_zero() => return Err(invalid("deadline")),
+        deadline => deadline,
+    };
+    Ok(Some(policy))
+}
+
+/// `#{attempts => N, deduplicated => Bool}`, reported alongside write results when a retry
+/// policy is configured or an idempotency key given.
+fn call_info(env: Env, attempts: Option<u32>, deduplicated: Option<bool>) -> Option<Term> {
+    let mut pairs = Vec::new();
+    if let Some(attempts) = attempts {
+        pairs.push((atoms::attempts().encode(env), attempts.encode(env)));
+    }
+    if let Some(deduplicated) = deduplicated {
+        pairs.push((atoms::deduplicated().encode(env), deduplicated.encode(env)));
+    }
+    (!pairs.is_empty()).then(|| Term::map_from_pairs(env, &pairs).expect("unique keys"))
+}
+
+/// Decode `#{window, capacity}`, `window` in milliseconds.
+fn decode_dedup_options(term: Term) -> Result<dedup::DedupOptions, String> {
+    if !term.is_map() {
+        return Err("invalid dedup option".to_string());
+    }
+    let env = term.get_env();
+    let get = |key: Atom| term.map_get(key.to_term(env)).ok();
+    let invalid = |name: &str| format!("invalid dedup option `{name}`");
+
+    let mut opts = dedup::DedupOptions::default();
+    match get(atoms::window()).map(|t| t.decode::<u64>()) {
+        Some(Ok(ms)) if ms > 0 => opts.window = Duration::from_millis(ms),
+        Some(_) => return Err(invalid("window")),
+        None => {}
+    }
+    match get(atoms::capacity()).map(|t| t.decode::<usize>()) {
+        Some(Ok(n)) if n > 0 => opts.capacity = n,
+        Some(_) => return Err(invalid("capacity")),
+        None => {}
+    }
+    Ok(opts)
+}
+
 #[rustler::nif(schedule = "DirtyIo")]
 fn connect(opts: Term) -> NifResult<Term> {
     let env = opts.get_env();
@@ -99,52 +864,120 @@ fn connect(opts: Term) -> NifResult<Term> {
 
     let endpoints: Vec<String> = endpoints_term.decode()?;
     let dbname: String = dbname_term.decode()?;
+    // The same cluster whatever the order its endpoints are listed in
+    let mut sorted_endpoints = endpoints.clone();
+    sorted_endpoints.sort();
+    let dedup_scope = format!("{}/{dbname}", sorted_endpoints.join(","));
 
-    let client = if let Ok(tls_term) = opts.map_get(atoms::tls().to_term(env)) {
-        if tls_term.decode::<bool>()? {
-            let tls_verify = match opts.map_get(atoms::verify().to_term(env)) {
-                Ok(verify_term) => match decode_tls_verify(verify_term) {
-                    Ok(verify) => verify,
-                    Err(err) => return Ok((atoms::error(), err).encode(env)),
-                },
-                Err(_) => TlsVerify::VerifyPeer,
-            };
-            let server_ca_cert_path = opts
-                .map_get(atoms::ca_cert().to_term(env))
-                .and_then(|t| t.decode())
-                .unwrap_or_default();
-            let client_cert_path = opts
-                .map_get(atoms::client_cert().to_term(env))
-                .and_then(|t| t.decode())
-                .unwrap_or_default();
-            let client_key_path = opts
-                .map_get(atoms::client_key().to_term(env))
-                .and_then(|t| t.decode())
-                .unwrap_or_default();
-
-            let tls_option = ClientTlsOption {
-                server_ca_cert_path,
-                client_cert_path,
-                client_key_path,
-                cipher_suites: opts
-                    .map_get(atoms::cipher_suites().to_term(env))
-                    .and_then(|t| t.decode())
-                    .unwrap_or_default(),
-            };
+    if endpoints.is_empty() {
+        return Ok((atoms::error(), "no endpoints").encode(env));
+    }
 
-            match Client::with_tls_and_urls_with_verify(endpoints, tls_option, tls_verify) {
-                Ok(c) => c,
-                Err(e) => return Ok((atoms::error(), e.to_string()).encode(env)),
+    let fips_mode = match opts.map_get(atoms::fips_mode().to_term(env)) {
+        Ok(term) => match decode_fips_mode(term) {
+            Ok(fips_mode) => fips_mode,
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => fips::FipsMode::Auto,
+    };
+    let fips = fips_mode.enforced();
+
+    let mut tls = None;
+    if let Ok(tls_term) = opts.map_get(atoms::tls().to_term(env)) {
+        if tls_term.decode::<bool>()? {
+            let config = decode_tls_config(opts, None).and_then(|mut config| {
+                if fips {
+                    fips::enforce(&mut config)?;
+                    fips::install_provider(fips_mode)?;
+                }
+                Ok(config)
+            });
+            match config {
+                Ok(config) => tls = Some(tls::Tls::new(config, fips)),
+                Err(err) => return Ok((atoms::error(), err).encode(env)),
             }
-        } else {
-            Client::with_urls(endpoints)
         }
-    } else {
-        Client::with_urls(endpoints)
+    }
+    if tls.is_none() && fips_mode == fips::FipsMode::Required {
+        let err = tls::ConfigError::new(atoms::tls(), "required by fips_mode");
+        return Ok((atoms::error(), err).encode(env));
+    }
+    let tls_config = match tls.as_ref().map(tls::Tls::client_config).transpose() {
+        Ok(tls_config) => tls_config,
+        Err(e) => return Ok((atoms::error(), e).encode(env)),
+    };
+
+    let compression = match opts.map_get(atoms::compression().to_term(env)) {
+        Ok(term) => match decode_compression(term) {
+            Ok(compression) => compression,
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => compression::Compression::None,
+    };
+    let channel = match decode_channel_config(opts, compression) {
+        Ok(channel) => channel,
+        Err(err) => return Ok((atoms::error(), err).encode(env)),
+    };
+
+    let proxy = match opts.map_get(atoms::proxy().to_term(env)) {
+        Ok(term) => match decode_proxy(term) {
+            Ok(proxy) => Some(Arc::new(proxy)),
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => None,
+    };
+
+    // One client per endpoint, so requests can be routed away from unhealthy ones
+    let mut endpoint_list = Vec::with_capacity(endpoints.len());
+    for url in endpoints {
+        let transport = match build_client(
+            &url,
+            channel.as_ref(),
+            tls_config.as_ref(),
+            proxy.as_ref(),
+            &runtime,
+        ) {
+            Ok(transport) => transport,
+            Err(e) => return Ok((atoms::error(), e).encode(env)),
+        };
+        endpoint_list.push(health::Endpoint::new(
+            url,
+            transport,
+            runtime.handle().clone(),
+        ));
+    }
+
+    let balance = match opts.map_get(atoms::balance().to_term(env)) {
+        Ok(term) => match decode_balance(term) {
+            Ok(balance) => balance,
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => health::Balance::RoundRobin,
+    };
+    // `infinity` disables health checking
+    let health_check_interval = match opts.map_get(atoms::health_check_interval().to_term(env)) {
+        Ok(term) if term.decode::<Atom>().is_ok_and(|a| a == atoms::infinity()) => None,
+        Ok(term) => match term.decode::<u64>() {
+            Ok(ms) if ms > 0 => Some(Duration::from_millis(ms)),
+            _ => {
+                let err = "invalid health_check_interval option";
+                return Ok((atoms::error(), err).encode(env));
+            }
+        },
+        Err(_) => Some(Duration::from_millis(10_000)),
+    };
+    let health_check_timeout = match opts.map_get(atoms::health_check_timeout().to_term(env)) {
+        Ok(term) => match term.decode::<u64>() {
+            Ok(ms) if ms > 0 => Duration::from_millis(ms),
+            _ => {
+                let err = "invalid health_check_timeout option";
+                return Ok((atoms::error(), err).encode(env));
+            }
+        },
+        Err(_) => Duration::from_millis(3_000),
     };
+    let endpoints = Arc::new(health::Endpoints::new(endpoint_list, balance));
 
-    let mut db = Database::new_with_dbname(dbname, client.clone());
-    let mut auth = None;
     let ts_column = opts
         .map_get(atoms::ts_column().to_term(env))
         .ok()
@@ -153,60 +986,330 @@ fn connect(opts: Term) -> NifResult<Term> {
         .map_get(atoms::ttl().to_term(env))
         .ok()
         .and_then(|term| term.decode().ok());
+    let default_timestamp = match opts.map_get(atoms::default_timestamp().to_term(env)) {
+        Ok(term) => match decode_default_timestamp(term) {
+            Ok(default_timestamp) => Some(default_timestamp),
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => None,
+    };
+    let coercion = match opts.map_get(atoms::coercion().to_term(env)) {
+        Ok(term) => match decode_coercion(term) {
+            Ok(coercion) => coercion,
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => types::Coercion::Strict,
+    };
+    let schema_hints = match opts.map_get(atoms::schema_hints().to_term(env)) {
+        Ok(term) => match decode_schema_hints(term) {
+            Ok(schema_hints) => schema_hints,
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => util::SchemaHints::new(),
+    };
+    let convert_opts = util::ConvertOptions {
+        default_timestamp,
+        coercion,
+        schema_hints,
+    };
+    let retry = match opts.map_get(atoms::retry().to_term(env)) {
+        Ok(term) => match decode_retry_policy(term) {
+            Ok(retry) => retry,
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => None,
+    };
 
-    if let Ok(username_term) = opts.map_get(atoms::username().to_term(env)) {
-        if let Ok(password_term) = opts.map_get(atoms::password().to_term(env)) {
-            let username: String = username_term.decode()?;
-            let password: String = password_term.decode()?;
-            let auth_scheme = AuthScheme::Basic(Basic { username, password });
-            db.set_auth(auth_scheme.clone());
-            auth = Some(auth_scheme);
-        }
+    let tracing = match opts.map_get(atoms::otlp_endpoint().to_term(env)) {
+        Ok(term) => match term
+            .decode::<String>()
+            .map_err(|_| "invalid otlp_endpoint option".to_string())
+            .and_then(|endpoint| trace::Tracing::new(&runtime, &endpoint))
+        {
+            Ok(tracing) => Some(Arc::new(tracing)),
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => None,
+    };
+
+    let auth: SharedAuth = match decode_auth(opts) {
+        Ok(auth) => Arc::new(RwLock::new(auth)),
+        Err(err) => return Ok((atoms::error(), err).encode(env)),
+    };
+
+    let spool = match opts.map_get(atoms::spool().to_term(env)) {
+        Ok(term) => match decode_spool_options(term).and_then(spool::Spool::open) {
+            Ok(spool) => {
+                spool::spawn_replay(
+                    &spool,
+                    &endpoints,
+                    &runtime,
+                    dbname.clone(),
+                    auth.clone(),
+                    ttl.clone(),
+                );
+                Some(spool)
+            }
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => None,
+    };
+
+    let dedup_opts = match opts.map_get(atoms::dedup().to_term(env)) {
+        Ok(term) => match decode_dedup_options(term) {
+            Ok(dedup_opts) => dedup_opts,
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => dedup::DedupOptions::default(),
+    };
+    let limits = match opts.map_get(atoms::max_in_flight().to_term(env)) {
+        Ok(term) => match decode_limits(term, "max_in_flight") {
+            Ok(limits) => limits,
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => limits::Limits::default(),
+    };
+    let stream_limits = match opts.map_get(atoms::stream_max_in_flight().to_term(env)) {
+        Ok(term) => match decode_limits(term, "stream_max_in_flight") {
+            Ok(limits) => limits,
+            Err(err) => return Ok((atoms::error(), err).encode(env)),
+        },
+        Err(_) => limits::Limits::default(),
+    };
+
+    if let Some(health_check_interval) = health_check_interval {
+        health::spawn_health_check(
+            &endpoints,
+            &runtime,
+            dbname.clone(),
+            auth.clone(),
+            health_check_interval,
+            health_check_timeout,
+        );
     }
 
     let resource = ResourceArc::new(GreptimeResource {
-        db,
-        client,
+        endpoints,
+        dbname,
         auth,
         ts_column,
         ttl,
+        convert_opts,
+        retry,
+        tls: Mutex::new(tls),
+        channel,
+        proxy,
+        compression,
+        metrics: Default::default(),
+        tracing,
+        spool,
+        dedup: dedup::Dedup::shared(dedup_scope, dedup_opts),
+        limiter: Arc::new(limits::Limiter::new(limits)),
+        stream_limits,
         runtime,
     });
     Ok((atoms::ok(), resource).encode(env))
 }
 
 #[rustler::nif(schedule = "DirtyIo")]
-fn disconnect(env: Env, _resource: ResourceArc<GreptimeResource>) -> NifResult<Term> {
+fn disconnect(env: Env, resource: ResourceArc<GreptimeResource>) -> NifResult<Term> {
+    if let Some(tracing) = &resource.tracing {
+        tracing.shutdown();
+    }
     // The resource will be automatically dropped when this function returns
     // and all Arc references are released. The Tokio runtime will be shut down
     // gracefully when the last Arc<Runtime> is dropped.
     Ok(atoms::ok().encode(env))
 }
 
+#[rustler::nif]
+fn connection_status(env: Env, resource: ResourceArc<GreptimeResource>) -> NifResult<Term> {
+    Ok((atoms::ok(), resource.endpoints.status()).encode(env))
+}
+
+/// Replace the credentials of a live connection.
+///
+/// Applies to every request started afterwards, including new streams. Open streams keep the
+/// credentials they were started with.
+#[rustler::nif]
+fn update_auth<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<GreptimeResource>,
+    creds: Term<'a>,
+) -> NifResult<Term<'a>> {
+    match decode_auth(creds) {
+        Ok(Some(auth)) => {
+            *resource.auth.write().unwrap() = Some(auth);
+            Ok(atoms::ok().encode(env))
+        }
+        Ok(None) => Ok((atoms::error(), "no credentials").encode(env)),
+        Err(err) => Ok((atoms::error(), err).encode(env)),
+    }
+}
+
+/// Rebuild the clients of a TLS connection with the certificates reloaded, from the given
+/// options merged over the current ones. Paths are read again even when unchanged.
+///
+/// Open streams keep the channel they were started on.
+#[rustler::nif(schedule = "DirtyIo")]
+fn reload_tls<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<GreptimeResource>,
+    opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let mut current = resource.tls.lock().unwrap();
+    let Some(current) = current.as_mut() else {
+        return Ok((atoms::error(), "tls is not enabled").encode(env));
+    };
+
+    let config = decode_tls_config(opts, Some(&current.config)).and_then(|mut config| {
+        if current.fips {
+            fips::enforce(&mut config)?;
+        }
+        Ok(config)
+    });
+    let reloaded = match config {
+        Ok(config) => tls::Tls::new(config, current.fips),
+        Err(err) => return Ok((atoms::error(), err).encode(env)),
+    };
+    let tls_config = match reloaded.client_config() {
+        Ok(tls_config) => tls_config,
+        Err(e) => return Ok((atoms::error(), e).encode(env)),
+    };
+    if let Err(e) = resource.endpoints.rebuild(|url| {
+        build_client(
+            url,
+            resource.channel.as_ref(),
+            Some(&tls_config),
+            resource.proxy.as_ref(),
+            &resource.runtime,
+        )
+    }) {
+        return Ok((atoms::error(), e).encode(env));
+    }
+
+    *current = reloaded;
+    Ok(atoms::ok().encode(env))
+}
+
 #[rustler::nif(schedule = "DirtyIo")]
-fn execute(env: Env, resource: ResourceArc<GreptimeResource>, sql: String) -> NifResult<Term> {
-    let db = &resource.db;
+fn ping(env: Env, resource: ResourceArc<GreptimeResource>, timeout_ms: u64) -> NifResult<Term> {
+    let db = resource.database();
+    let started = Instant::now();
+    let result = resource.runtime.block_on(tokio::time::timeout(
+        Duration::from_millis(timeout_ms),
+        health::server_version(&db),
+    ));
+
+    match result {
+        Ok(Ok(version)) => {
+            let latency_us = started.elapsed().as_micros() as u64;
+            let version = match version {
+                Some(version) => version.encode(env),
+                None => rustler::types::atom::undefined().encode(env),
+            };
+            let info = Term::map_from_pairs(
+                env,
+                &[
+                    (atoms::latency_us().encode(env), latency_us.encode(env)),
+                    (atoms::version().encode(env), version),
+                ],
+            )?;
+            Ok((atoms::ok(), info).encode(env))
+        }
+        Ok(Err(e)) => Ok((atoms::error(), e).encode(env)),
+        Err(_) => Ok((atoms::error(), atoms::timeout()).encode(env)),
+    }
+}
+
+/// Counters of a connection or of a stream.
+#[rustler::nif]
+fn stats<'a>(env: Env<'a>, resource: Term<'a>) -> NifResult<Term<'a>> {
+    if let Ok(stream) = resource.decode::<ResourceArc<StreamWriterResource>>() {
+        let mut pairs = stream.metrics.pairs(env);
+        pairs.extend(stream.limiter.pairs(env));
+        let stats = Term::map_from_pairs(env, &pairs)?;
+        return Ok((atoms::ok(), stats).encode(env));
+    }
+    let resource: ResourceArc<GreptimeResource> = resource.decode()?;
+    let compression = match resource.compression {
+        compression::Compression::None => atoms::none(),
+        compression::Compression::Gzip => atoms::gzip(),
+        compression::Compression::Zstd => atoms::zstd(),
+    };
+
+    let mut pairs = resource.metrics.pairs(env);
+    pairs.extend(resource.limiter.pairs(env));
+    pairs.push((
+        atoms::deduplicated().encode(env),
+        resource.dedup.hits().encode(env),
+    ));
+    pairs.push((atoms::compression().encode(env), compression.encode(env)));
+    let stats = Term::map_from_pairs(env, &pairs)?;
+    Ok((atoms::ok(), stats).encode(env))
+}
+
+/// Backlog and counters of the connection's spool.
+#[rustler::nif]
+fn spool_status(env: Env, resource: ResourceArc<GreptimeResource>) -> NifResult<Term> {
+    match &resource.spool {
+        Some(spool) => Ok((atoms::ok(), spool.status(env)).encode(env)),
+        None => Ok((atoms::error(), "spool is not enabled").encode(env)),
+    }
+}
+
+/// The `db` per-call option, naming another database than the connection's.
+fn decode_db(opts: Term) -> NifResult<Option<String>> {
+    let env = opts.get_env();
+    opts.map_get(atoms::db().to_term(env))
+        .ok()
+        .map(|t| t.decode::<String>())
+        .transpose()
+        .map_err(|_| rustler::Error::RaiseTerm(Box::new("invalid db option")))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn execute<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<GreptimeResource>,
+    sql: String,
+    opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let trace = decode_trace_context(opts).map_err(|e| rustler::Error::RaiseTerm(Box::new(e)))?;
+    let dbname = decode_db(opts)?.unwrap_or_else(|| resource.dbname.clone());
     let runtime = &resource.runtime;
+    resource.metrics.queries.fetch_add(1, Ordering::Relaxed);
+    let _in_flight = metrics::Gauge::inc(&resource.metrics.in_flight);
+    let started = Instant::now();
+    let span = trace::Span::start(
+        resource.tracing.as_deref(),
+        trace.as_ref(),
+        "greptimedb.query",
+    );
 
     // Collect RecordBatches first (Env is not Send)
-    let result = runtime.block_on(async {
-        use futures::StreamExt;
-        match db.query(&sql).await {
-            Ok(mut stream) => {
-                let mut batches = Vec::new();
-                while let Some(batch_res) = stream.next().await {
-                    match batch_res {
-                        Ok(batch) => {
-                            batches.push(batch);
-                        }
-                        Err(e) => return Err(e),
-                    }
-                }
-                Ok(batches)
+    let result = match span.headers() {
+        // The database can't carry a trace context, the query is sent on the endpoint's
+        // channel instead
+        Some(headers) => runtime.block_on(async {
+            let channel = resource.endpoints.pick().channel()?;
+            let header = resource.request_header(&dbname);
+            let compression = resource.compression.encoding();
+            trace::query(channel, header, &sql, compression, headers).await
+        }),
+        None => runtime.block_on(async {
+            use futures::StreamExt;
+            let db = resource.database_in(&dbname);
+            let mut stream = db.query(&sql).await?;
+            let mut batches = Vec::new();
+            while let Some(batch) = stream.next().await {
+                batches.push(batch?);
             }
-            Err(e) => Err(e),
-        }
-    });
+            Ok::<_, retry::Failure>(batches)
+        }),
+    };
+    span.end(&result);
+    resource.metrics.query_latency.observe(started.elapsed());
 
     match result {
         Ok(batches) => {
@@ -217,12 +1320,81 @@ fn execute(env: Env, resource: ResourceArc<GreptimeResource>, sql: String) -> Ni
             }
             Ok((atoms::ok(), all_rows).encode(env))
         }
-        Err(e) => Ok((atoms::error(), e.to_string()).encode(env)),
+        Err(e) => {
+            resource.metrics.errors.record_err(&e);
+            Ok((atoms::error(), e).encode(env))
+        }
     }
 }
 
-async fn fetch_table_schema(db: &Database, table_name: &str) -> Result<TableSchema, String> {
-    let sql = format!("DESCRIBE {table_name}");
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_databases(env: Env, resource: ResourceArc<GreptimeResource>) -> NifResult<Term> {
+    let db = resource.database();
+    match resource.runtime.block_on(introspect::list_databases(&db)) {
+        Ok(names) => Ok((atoms::ok(), names).encode(env)),
+        Err(e) => Ok((atoms::error(), e).encode(env)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn list_tables(
+    env: Env,
+    resource: ResourceArc<GreptimeResource>,
+    dbname: String,
+) -> NifResult<Term> {
+    let db = resource.database();
+    match resource
+        .runtime
+        .block_on(introspect::list_tables(&db, &dbname))
+    {
+        Ok(names) => Ok((atoms::ok(), names).encode(env)),
+        Err(e) => Ok((atoms::error(), e).encode(env)),
+    }
+}
+
+/// Columns of a table of the connection's database, with their types and indexes.
+#[rustler::nif(schedule = "DirtyIo")]
+fn describe_table(
+    env: Env,
+    resource: ResourceArc<GreptimeResource>,
+    table: String,
+) -> NifResult<Term> {
+    let db = resource.database();
+    match resource
+        .runtime
+        .block_on(introspect::describe_table(&db, &resource.dbname, &table))
+    {
+        Ok(columns) => Ok((atoms::ok(), columns).encode(env)),
+        Err(e) => Ok((atoms::error(), e).encode(env)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn table_exists(
+    env: Env,
+    resource: ResourceArc<GreptimeResource>,
+    table: String,
+) -> NifResult<Term> {
+    let db = resource.database();
+    match resource
+        .runtime
+        .block_on(introspect::table_exists(&db, &resource.dbname, &table))
+    {
+        Ok(exists) => Ok((atoms::ok(), exists).encode(env)),
+        Err(e) => Ok((atoms::error(), e).encode(env)),
+    }
+}
+
+async fn fetch_table_schema(
+    db: &Database,
+    dbname: &str,
+    table_name: &str,
+) -> Result<TableSchema, String> {
+    let sql = format!(
+        "DESCRIBE TABLE {}.{}",
+        introspect::identifier(dbname),
+        introspect::identifier(table_name)
+    );
     let mut stream = db.query(&sql).await.map_err(|e| e.to_string())?;
 
     let mut table_schema = TableSchema::builder()
@@ -274,6 +1446,7 @@ async fn fetch_table_schema(db: &Database, table_name: &str) -> Result<TableSche
                 "String" => ColumnDataType::String,
                 "Boolean" => ColumnDataType::Boolean,
                 "Binary" => ColumnDataType::Binary,
+                "Json" => ColumnDataType::Json,
                 "Date" => ColumnDataType::Date,
                 "Datetime" => ColumnDataType::Datetime,
                 "TimestampSecond" => ColumnDataType::TimestampSecond,
@@ -295,81 +1468,429 @@ async fn fetch_table_schema(db: &Database, table_name: &str) -> Result<TableSche
     Ok(table_schema)
 }
 
+/// Per-call options of `insert`.
+struct InsertOptions {
+    /// Write the rows that convert and report the others instead of failing the batch.
+    partial_write: bool,
+    /// Merged over the connection's `schema_hints`.
+    schema_hints: Option<util::SchemaHints>,
+    trace: Option<trace::TraceContext>,
+    /// Identifies the batch, whose resends are skipped once it was written.
+    idempotency_key: Option<String>,
+    /// Written to instead of the connection's database, bypassing the spool.
+    db: Option<String>,
+}
+
+impl InsertOptions {
+    fn decode(opts: Term) -> NifResult<Self> {
+        let env = opts.get_env();
+        let partial_write = opts
+            .map_get(atoms::partial_write().to_term(env))
+            .ok()
+            .map(|t| t.decode::<bool>())
+            .transpose()?
+            .unwrap_or(false);
+        let schema_hints = opts
+            .map_get(atoms::schema_hints().to_term(env))
+            .ok()
+            .map(decode_schema_hints)
+            .transpose()
+            .map_err(|e| rustler::Error::RaiseTerm(Box::new(e)))?;
+        let trace =
+            decode_trace_context(opts).map_err(|e| rustler::Error::RaiseTerm(Box::new(e)))?;
+        let idempotency_key = opts
+            .map_get(atoms::idempotency_key().to_term(env))
+            .ok()
+            .map(|t| t.decode::<String>())
+            .transpose()
+            .map_err(|_| rustler::Error::RaiseTerm(Box::new("invalid idempotency_key option")))?;
+        Ok(InsertOptions {
+            partial_write,
+            schema_hints,
+            trace,
+            idempotency_key,
+            db: decode_db(opts)?,
+        })
+    }
+
+    /// The connection's conversion options, with the per-call ones applied.
+    fn convert_opts<'r>(&self, resource: &'r GreptimeResource) -> Cow<'r, util::ConvertOptions> {
+        match &self.schema_hints {
+            Some(hints) => {
+                let mut convert_opts = resource.convert_opts.clone();
+                convert_opts.schema_hints.extend(hints.clone());
+                Cow::Owned(convert_opts)
+            }
+            None => Cow::Borrowed(&resource.convert_opts),
+        }
+    }
+}
+
+/// Convert rows to the schema of their table, or to one inferred from the rows when the
+/// table doesn't exist yet.
+fn convert_insert_rows<'a>(
+    resource: &GreptimeResource,
+    table_schema: Option<&TableSchema>,
+    rows_term: Vec<Term<'a>>,
+    convert_opts: &util::ConvertOptions,
+    errors: Option<&mut Vec<util::RowError<'a>>>,
+) -> NifResult<(Vec<ColumnSchema>, Vec<Row>)> {
+    match table_schema {
+        Some(s) => {
+            // Table exists, use server schema
+            util::terms_to_proto_rows_using_schema(s, rows_term, convert_opts, errors)
+                .map(|proto_rows| (proto_columns(s), proto_rows))
+        }
+        None => {
+            // Table might not exist, infer schema locally
+            util::terms_to_schema_and_rows(
+                rows_term,
+                resource.ts_column.as_deref().unwrap_or("ts"),
+                convert_opts,
+                errors,
+            )
+        }
+    }
+}
+
+fn proto_columns(schema: &TableSchema) -> Vec<ColumnSchema> {
+    schema
+        .columns()
+        .iter()
+        .map(|c| ColumnSchema {
+            column_name: c.name.clone(),
+            datatype: c.data_type as i32,
+            semantic_type: c.semantic_type as i32,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Where an insert attempt is sent.
+enum InsertVia {
+    Database(Database),
+    /// The database can't carry a trace context, the request is sent on the endpoint's
+    /// channel instead.
+    Traced(
+        Result<tonic::transport::Channel, String>,
+        RequestHeader,
+        HashMap<String, String>,
+    ),
+}
+
 #[rustler::nif(schedule = "DirtyIo")]
 fn insert<'a>(
     env: Env<'a>,
     resource: ResourceArc<GreptimeResource>,
     table: String,
     rows_term: Vec<Term<'a>>,
+    opts: Term<'a>,
 ) -> NifResult<Term<'a>> {
+    let opts = InsertOptions::decode(opts)?;
+    let dbname = opts.db.as_deref().unwrap_or(&resource.dbname);
+    // Spooled requests are replayed into the connection's database, there would be nowhere
+    // to keep the insert into another one if it failed
+    if opts.db.is_some() && resource.spool.is_some() {
+        return Err(rustler::Error::RaiseTerm(Box::new(
+            "db option not allowed with a spool",
+        )));
+    }
+    let spool = resource.spool.as_deref();
+    let ticket = match opts.idempotency_key.clone() {
+        Some(key) => match resource.dedup.claim(&format!("{dbname}.{table}"), key) {
+            dedup::Claim::New(ticket) => Some(ticket),
+            dedup::Claim::Done(affected) => {
+                // Nothing was sent, there are no attempts to report
+                let info = call_info(env, None, Some(true));
+                let term = if opts.partial_write {
+                    (atoms::ok(), affected, Vec::<Term>::new(), info).encode(env)
+                } else {
+                    (atoms::ok(), affected, info).encode(env)
+                };
+                return Ok(term);
+            }
+            dedup::Claim::InProgress => {
+                return Ok((atoms::error(), atoms::in_progress()).encode(env))
+            }
+            dedup::Claim::Full => return Ok((atoms::error(), atoms::overloaded()).encode(env)),
+        },
+        None => None,
+    };
+    let Ok(_permit) = resource.limiter.acquire(1, util::approx_size(&rows_term)) else {
+        return Ok((atoms::error(), atoms::overloaded()).encode(env));
+    };
+    let metrics = &resource.metrics;
+    metrics.inserts.fetch_add(1, Ordering::Relaxed);
+    let _in_flight = metrics::Gauge::inc(&metrics.in_flight);
+    let started = Instant::now();
+    let span = trace::Span::start(
+        resource.tracing.as_deref(),
+        opts.trace.as_ref(),
+        "greptimedb.insert",
+    );
+    let reply = |result: Result<spool::Written, retry::Failure>,
+                 row_errors: Vec<util::RowError<'a>>,
+                 attempts: u32| {
+        span.end(&result);
+        metrics.insert_latency.observe(started.elapsed());
+        metrics
+            .rows_rejected
+            .fetch_add(row_errors.len() as u64, Ordering::Relaxed);
+        metrics
+            .retries
+            .fetch_add(attempts.saturating_sub(1) as u64, Ordering::Relaxed);
+        match &result {
+            Ok(spool::Written::Affected(affected)) => {
+                metrics
+                    .rows_written
+                    .fetch_add(*affected as u64, Ordering::Relaxed);
+            }
+            Ok(spool::Written::Spooled) => {}
+            Err(e) => metrics.errors.record_err(e),
+        }
+        // Failed or spooled, the ticket is dropped, releasing the key for the batch to be
+        // sent again
+        if let (Some(ticket), Ok(spool::Written::Affected(affected))) = (ticket, &result) {
+            ticket.ack(*affected);
+        }
+        let info = call_info(
+            env,
+            resource.retry.as_ref().map(|_| attempts),
+            opts.idempotency_key
+                .as_ref()
+                .filter(|_| result.is_ok())
+                .map(|_| false),
+        );
+        let term = match (result, info) {
+            (Ok(affected), None) if opts.partial_write => {
+                (atoms::ok(), affected, row_errors).encode(env)
+            }
+            (Ok(affected), None) => (atoms::ok(), affected).encode(env),
+            (Ok(affected), Some(info)) if opts.partial_write => {
+                (atoms::ok(), affected, row_errors, info).encode(env)
+            }
+            (Ok(affected), Some(info)) => (atoms::ok(), affected, info).encode(env),
+            (Err(e), None) => (atoms::error(), e).encode(env),
+            (Err(e), Some(info)) => (atoms::error(), (e, info)).encode(env),
+        };
+        Ok(term)
+    };
+
     if rows_term.is_empty() {
-        return Ok((atoms::ok(), 0).encode(env));
+        return reply(Ok(spool::Written::Affected(0)), Vec::new(), 0);
     }
 
+    let convert_opts = opts.convert_opts(&resource);
+    let mut row_errors = Vec::new();
+    let errors = opts.partial_write.then_some(&mut row_errors);
+
     let runtime = &resource.runtime;
+    let db = resource.database_in(dbname);
 
     use greptimedb_ingester::api::v1::{RowInsertRequest, RowInsertRequests, Rows};
 
     // 1. Try Fetch Schema from Server
+    let schema_span = span.child("greptimedb.schema_fetch");
     let table_schema_res: Result<TableSchema, String> =
-        runtime.block_on(fetch_table_schema(&resource.db, &table));
+        runtime.block_on(resource.fetch_table_schema(&db, dbname, &table));
+    schema_span.end(&table_schema_res);
 
-    let (schema, rows) = match table_schema_res {
-        Ok(s) => {
-            // Table exists, use server schema
-            let proto_rows = util::terms_to_proto_rows_using_schema(&s, rows_term)?;
-
-            use greptimedb_ingester::api::v1::ColumnSchema;
-            let schema_cols: Vec<ColumnSchema> = s
-                .columns()
-                .iter()
-                .map(|c| ColumnSchema {
-                    column_name: c.name.clone(),
-                    datatype: c.data_type as i32,
-                    semantic_type: c.semantic_type as i32,
-                    ..Default::default()
-                })
-                .collect();
-
-            (schema_cols, proto_rows)
-        }
-        Err(_) => {
-            // Table might not exist, infer schema locally
-            util::terms_to_schema_and_rows(
-                rows_term,
-                resource.ts_column.as_deref().unwrap_or("ts"),
-            )?
+    let convert_span = span.child("greptimedb.convert");
+    let converted = convert_insert_rows(
+        &resource,
+        table_schema_res.as_ref().ok(),
+        rows_term,
+        &convert_opts,
+        errors,
+    );
+    convert_span.end(&converted.as_ref().map_err(|_| "conversion failed"));
+    let (schema, rows) = match converted {
+        Ok(converted) => converted,
+        Err(e) => {
+            metrics.errors.record(metrics::ErrorKind::Conversion);
+            span.end(&Err::<(), _>("conversion failed"));
+            return Err(e);
         }
     };
 
+    // Every row was rejected, nothing to send
+    if rows.is_empty() {
+        return reply(Ok(spool::Written::Affected(0)), row_errors, 0);
+    }
+
     // 2. Construct Request
-    let insert_request = RowInsertRequests {
+    let mut insert_request = RowInsertRequests {
         inserts: vec![RowInsertRequest {
             table_name: table,
             rows: Some(Rows { schema, rows }),
         }],
     };
 
-    // 3. Insert using Database
-    let result: Result<u32, String> = runtime.block_on(async {
-        if let Some(ttl) = &resource.ttl {
-            resource
-                .db
-                .insert_with_hints(insert_request, &[("ttl", ttl.as_str())])
-                .await
-                .map_err(|e| e.to_string())
+    // Queue behind the spooled requests, to keep them in order
+    if let Some(spool) = spool.filter(|spool| spool.pending() > 0) {
+        let result = spool
+            .push(&insert_request)
+            .map(|()| spool::Written::Spooled)
+            .map_err(retry::Failure::from);
+        return reply(result, row_errors, 0);
+    }
+
+    // 3. Insert using Database, retrying transient failures on a freshly picked endpoint
+    let ttl = resource.ttl.as_deref();
+    let compression = resource.compression.encoding();
+    let keep_request = resource.retry.is_some() || spool.is_some();
+    let rpc_span = span.child("greptimedb.rpc");
+    let trace_headers = rpc_span.headers();
+    let mut db = Some(db);
+    let (result, attempts) = runtime.block_on(retry::run(resource.retry.as_ref(), || {
+        let via = match &trace_headers {
+            Some(headers) => InsertVia::Traced(
+                resource.endpoints.pick().channel(),
+                resource.request_header(dbname),
+                headers.clone(),
+            ),
+            None => InsertVia::Database(db.take().unwrap_or_else(|| resource.database_in(dbname))),
+        };
+        // Only keep a copy of the rows around when another attempt or the spool may need them
+        let request = if keep_request {
+            insert_request.clone()
         } else {
-            resource
-                .db
-                .insert(insert_request)
-                .await
-                .map_err(|e| e.to_string())
+            std::mem::take(&mut insert_request)
+        };
+        metrics.bytes_sent.fetch_add(
+            prost::Message::encoded_len(&request) as u64,
+            Ordering::Relaxed,
+        );
+        async move {
+            match via {
+                InsertVia::Database(db) => match ttl {
+                    Some(ttl) => db.insert_with_hints(request, &[("ttl", ttl)]).await,
+                    None => db.insert(request).await,
+                }
+                .map_err(retry::Failure::from),
+                InsertVia::Traced(channel, header, headers) => {
+                    trace::insert(channel?, header, request, ttl, compression, headers).await
+                }
+            }
         }
-    });
+    }));
+    rpc_span.end(&result);
 
-    match result {
-        Ok(affected) => Ok((atoms::ok(), affected).encode(env)),
-        Err(e) => Ok((atoms::error(), e).encode(env)),
+    let result = match (result, spool) {
+        (Err(e), Some(spool)) if spool::recoverable(&e) => match spool.push(&insert_request) {
+            Ok(()) => {
+                // Still a failure of the connection, though not of the call
+                metrics.errors.record_err(&e);
+                Ok(spool::Written::Spooled)
+            }
+            Err(err) => {
+                log::warn!("failed to spool an insert: {err}");
+                Err(e)
+            }
+        },
+        (result, _) => result.map(spool::Written::Affected),
+    };
+    reply(result, row_errors, attempts)
+}
+
+/// Delete the rows identified by their tags and timestamp, their fields are ignored.
+#[rustler::nif(schedule = "DirtyIo")]
+fn delete<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<GreptimeResource>,
+    table: String,
+    rows_term: Vec<Term<'a>>,
+) -> NifResult<Term<'a>> {
+    use greptime_proto::v1::greptime_request::Request;
+    use greptime_proto::v1::{RowDeleteRequest, RowDeleteRequests, Rows};
+
+    if rows_term.is_empty() {
+        return Ok((atoms::ok(), 0).encode(env));
+    }
+    let Ok(_permit) = resource.limiter.acquire(1, util::approx_size(&rows_term)) else {
+        return Ok((atoms::error(), atoms::overloaded()).encode(env));
+    };
+    let runtime = &resource.runtime;
+    let metrics = &resource.metrics;
+    metrics.deletes.fetch_add(1, Ordering::Relaxed);
+    let _in_flight = metrics::Gauge::inc(&metrics.in_flight);
+    let started = Instant::now();
+
+    // There is nothing to delete from a table that doesn't exist
+    let table_schema = match runtime.block_on(resource.fetch_table_schema(
+        &resource.database(),
+        &resource.dbname,
+        &table,
+    )) {
+        Ok(table_schema) => table_schema,
+        Err(e) => return Ok((atoms::error(), e).encode(env)),
+    };
+    // A row without a timestamp names no row, it must not get a default one
+    let convert_opts = util::ConvertOptions {
+        default_timestamp: Some(util::DefaultTimestamp::Error),
+        ..resource.convert_opts.clone()
+    };
+    let (schema, rows) = convert_insert_rows(
+        &resource,
+        Some(&table_schema),
+        rows_term,
+        &convert_opts,
+        None,
+    )
+    .inspect_err(|_| metrics.errors.record(metrics::ErrorKind::Conversion))?;
+
+    // Only the primary key and the time index identify the rows
+    let keep: Vec<bool> = schema
+        .iter()
+        .map(|column| column.semantic_type != SemanticType::Field as i32)
+        .collect();
+    let schema = schema
+        .into_iter()
+        .zip(&keep)
+        .filter_map(|(column, keep)| keep.then_some(column))
+        .collect();
+    let rows = rows
+        .into_iter()
+        .map(|row| Row {
+            values: row
+                .values
+                .into_iter()
+                .zip(&keep)
+                .filter_map(|(value, keep)| keep.then_some(value))
+                .collect(),
+        })
+        .collect();
+    let request = RowDeleteRequests {
+        deletes: vec![RowDeleteRequest {
+            table_name: table,
+            rows: Some(Rows { schema, rows }),
+        }],
+    };
+
+    let compression = resource.compression.encoding();
+    let (result, attempts) =
+        runtime.block_on(retry::run(resource.retry.as_ref(), || {
+            let channel = resource.endpoints.pick().channel();
+            let header = resource.request_header(&resource.dbname);
+            let request = Request::RowDeletes(request.clone());
+            async move {
+                trace::handle(channel?, header, request, None, compression, HashMap::new()).await
+            }
+        }));
+    metrics.delete_latency.observe(started.elapsed());
+    metrics
+        .retries
+        .fetch_add(attempts.saturating_sub(1) as u64, Ordering::Relaxed);
+    if let Err(e) = &result {
+        metrics.errors.record_err(e);
+    }
+
+    let info = call_info(env, resource.retry.as_ref().map(|_| attempts), None);
+    match (result, info) {
+        (Ok(affected), None) => Ok((atoms::ok(), affected).encode(env)),
+        (Ok(affected), Some(info)) => Ok((atoms::ok(), affected, info).encode(env)),
+        (Err(e), None) => Ok((atoms::error(), e).encode(env)),
+        (Err(e), Some(info)) => Ok((atoms::error(), (e, info)).encode(env)),
     }
 }
 
@@ -379,12 +1900,16 @@ fn stream_start<'a>(
     resource: ResourceArc<GreptimeResource>,
     table: String,
     _first_row: Term<'a>,
+    opts: Term<'a>,
 ) -> NifResult<Term<'a>> {
+    let dbname = decode_db(opts)?.unwrap_or_else(|| resource.dbname.clone());
     let runtime = &resource.runtime;
+    let endpoint = resource.endpoints.pick();
 
     // 1. Fetch Schema from Server
-    let table_template_res: Result<TableSchema, String> =
-        runtime.block_on(fetch_table_schema(&resource.db, &table));
+    let table_template_res: Result<TableSchema, String> = runtime.block_on(
+        resource.fetch_table_schema(&resource.database_on(endpoint, &dbname), &dbname, &table),
+    );
     let table_template = match table_template_res {
         Ok(s) => s,
         Err(e) => return Ok((atoms::error(), e).encode(env)),
@@ -393,75 +1918,234 @@ fn stream_start<'a>(
     let schema_clone = table_template.clone();
 
     let result: Result<ResourceArc<StreamWriterResource>, String> = runtime.block_on(async {
-        let mut bulk_inserter = BulkInserter::new(resource.client.clone(), resource.db.dbname());
-        if let Some(auth) = &resource.auth {
-            bulk_inserter.set_auth(auth.clone());
-        }
-
-        // Create writer without compression for better CPU efficiency
-        let writer = bulk_inserter
-            .create_bulk_stream_writer(
-                &table_template,
-                Some(BulkWriteOptions::default().with_timeout(Duration::from_secs(30))),
-            )
-            .await
-            .map_err(|e| e.to_string())?;
-
-        // Pre-allocate buffer for optimal performance
-        // capacity: 10000 rows (good for 5K-10K batch sizes)
-        // row_buffer_size: 1024 (recommended for row-to-column transformation)
-        writer
-            .alloc_rows_buffer(5000, 1024)
-            .map_err(|e| e.to_string())?;
+        let client = endpoint.client();
+        let channel = endpoint.channel()?;
+        let writer = open_stream_writer(
+            client.clone(),
+            &dbname,
+            resource.current_auth(),
+            &table_template,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
 
         Ok(ResourceArc::new(StreamWriterResource {
             writer: tokio::sync::Mutex::new(Some(SendableBulkStreamWriter(writer))),
+            client,
+            channel,
+            dbname,
+            table,
+            auth: resource.auth.clone(),
             schema: schema_clone,
+            convert_opts: resource.convert_opts.clone(),
+            retry: resource.retry.clone(),
+            metrics: Default::default(),
+            connection_metrics: resource.metrics.clone(),
+            limiter: Arc::new(limits::Limiter::new(resource.stream_limits.clone())),
+            connection_limiter: resource.limiter.clone(),
+            tracing: resource.tracing.clone(),
             runtime: resource.runtime.clone(),
         }))
     });
 
     match result {
-        Ok(res) => Ok((atoms::ok(), res).encode(env)),
+        Ok(res) => {
+            resource
+                .metrics
+                .streams_open
+                .fetch_add(1, Ordering::Relaxed);
+            Ok((atoms::ok(), res).encode(env))
+        }
         Err(e) => Ok((atoms::error(), e).encode(env)),
     }
 }
 
+async fn open_stream_writer(
+    client: Client,
+    dbname: &str,
+    auth: Option<AuthScheme>,
+    schema: &TableSchema,
+) -> Result<BulkStreamWriter, greptimedb_ingester::Error> {
+    let mut bulk_inserter = BulkInserter::new(client, dbname);
+    if let Some(auth) = auth {
+        bulk_inserter.set_auth(auth);
+    }
+
+    // Create writer without compression for better CPU efficiency
+    let writer = bulk_inserter
+        .create_bulk_stream_writer(
+            schema,
+            Some(BulkWriteOptions::default().with_timeout(Duration::from_secs(30))),
+        )
+        .await?;
+
+    // Pre-allocate buffer for optimal performance
+    // capacity: 10000 rows (good for 5K-10K batch sizes)
+    // row_buffer_size: 1024 (recommended for row-to-column transformation)
+    writer.alloc_rows_buffer(5000, 1024)?;
+    Ok(writer)
+}
+
 #[rustler::nif(schedule = "DirtyIo")]
 fn stream_write<'a>(
     env: Env<'a>,
     resource: ResourceArc<StreamWriterResource>,
     rows_term: Vec<Term<'a>>,
+    opts: Term<'a>,
 ) -> NifResult<Term<'a>> {
-    let runtime = &resource.runtime;
-    let greptime_rows = util::terms_to_rows(&resource.schema, rows_term)?;
+    let trace = decode_trace_context(opts).map_err(|e| rustler::Error::RaiseTerm(Box::new(e)))?;
+    // Held against both the stream and its connection
+    let size = util::approx_size(&rows_term);
+    let Ok(_permit) = resource.limiter.acquire(1, size) else {
+        return Ok((atoms::error(), atoms::overloaded()).encode(env));
+    };
+    let Ok(_connection_permit) = resource.connection_limiter.acquire(1, size) else {
+        return Ok((atoms::error(), atoms::overloaded()).encode(env));
+    };
+    let metrics = &resource.metrics;
+    metrics.writes.fetch_add(1, Ordering::Relaxed);
+    let _in_flight = metrics::Gauge::inc(&metrics.in_flight);
+    let started = Instant::now();
 
-    let result: Result<(), String> = runtime.block_on(async {
-        let mut writer_guard = resource.writer.lock().await;
-        if let Some(writer_wrapper) = writer_guard.as_mut() {
-            let writer = &mut writer_wrapper.0;
-            let _request_id = writer
-                .write_rows_async(greptime_rows)
-                .await
-                .map_err(|e| e.to_string())?;
-            Ok(())
-        } else {
-            Err("Writer is closed".to_string())
+    let span = trace::Span::start(
+        resource.tracing.as_deref(),
+        trace.as_ref(),
+        "greptimedb.stream_write",
+    );
+    let (result, attempts) = match span.headers() {
+        Some(headers) => traced_stream_write(&resource, rows_term.clone(), headers)?,
+        None => untraced_stream_write(&resource, &rows_term)?,
+    };
+    span.end(&result);
+
+    metrics.write_latency.observe(started.elapsed());
+    metrics
+        .retries
+        .fetch_add(attempts.saturating_sub(1) as u64, Ordering::Relaxed);
+    match &result {
+        Ok(_) => {
+            metrics
+                .rows_written
+                .fetch_add(rows_term.len() as u64, Ordering::Relaxed);
         }
-    });
+        Err(e) => metrics.errors.record_err(e),
+    }
 
-    match result {
-        Ok(_) => Ok(atoms::ok().encode(env)),
-        Err(e) => Ok((atoms::error(), e).encode(env)),
+    let info = call_info(env, resource.retry.as_ref().map(|_| attempts), None);
+    match (result, info) {
+        (Ok(_), None) => Ok(atoms::ok().encode(env)),
+        (Ok(_), Some(info)) => Ok((atoms::ok(), info).encode(env)),
+        (Err(e), None) => Ok((atoms::error(), e).encode(env)),
+        (Err(e), Some(info)) => Ok((atoms::error(), (e, info)).encode(env)),
     }
 }
 
+fn untraced_stream_write(
+    resource: &StreamWriterResource,
+    rows_term: &[Term],
+) -> NifResult<(Result<(), retry::Failure>, u32)> {
+    let metrics = &resource.metrics;
+    let converted =
+        util::terms_to_rows(&resource.schema, rows_term.to_vec(), &resource.convert_opts);
+    let mut greptime_rows =
+        Some(converted.inspect_err(|_| metrics.errors.record(metrics::ErrorKind::Conversion))?);
+
+    // The write holds the writer lock, so attempts are never cancelled by the deadline
+    let (result, attempts) =
+        resource
+            .runtime
+            .block_on(retry::run_to_completion(resource.retry.as_ref(), || {
+                // Rows are consumed by the writer, later attempts convert them again
+                let retrying = greptime_rows.is_none();
+                let rows = greptime_rows.take().map_or_else(
+                    || {
+                        util::terms_to_rows(
+                            &resource.schema,
+                            rows_term.to_vec(),
+                            &resource.convert_opts,
+                        )
+                        .map_err(|_| "failed to convert rows".to_string())
+                    },
+                    Ok,
+                );
+                async move {
+                    let rows = rows?;
+                    let mut writer_guard = resource.writer.lock().await;
+                    let Some(writer_wrapper) = writer_guard.as_mut() else {
+                        return Err("Writer is closed".to_string().into());
+                    };
+                    if retrying {
+                        // The stream failed the last attempt, write on a new one
+                        let auth = resource.auth.read().unwrap().clone();
+                        let writer = open_stream_writer(
+                            resource.client.clone(),
+                            &resource.dbname,
+                            auth,
+                            &resource.schema,
+                        )
+                        .await?;
+                        *writer_wrapper = SendableBulkStreamWriter(writer);
+                    }
+                    let _request_id = writer_wrapper.0.write_rows_async(rows).await?;
+                    Ok(())
+                }
+            }));
+    Ok((result, attempts))
+}
+
+/// A stream can't carry a trace context, a traced write is sent as an insert of the rows
+/// into the stream's table instead.
+fn traced_stream_write(
+    resource: &StreamWriterResource,
+    rows_term: Vec<Term>,
+    headers: HashMap<String, String>,
+) -> NifResult<(Result<(), retry::Failure>, u32)> {
+    use greptimedb_ingester::api::v1::{RowInsertRequest, RowInsertRequests, Rows};
+
+    let metrics = &resource.metrics;
+    let rows = util::terms_to_proto_rows_using_schema(
+        &resource.schema,
+        rows_term,
+        &resource.convert_opts,
+        None,
+    )
+    .inspect_err(|_| metrics.errors.record(metrics::ErrorKind::Conversion))?;
+    let request = RowInsertRequests {
+        inserts: vec![RowInsertRequest {
+            table_name: resource.table.clone(),
+            rows: Some(Rows {
+                schema: proto_columns(&resource.schema),
+                rows,
+            }),
+        }],
+    };
+
+    let (result, attempts) = resource
+        .runtime
+        .block_on(retry::run(resource.retry.as_ref(), || {
+            let auth = resource.auth.read().unwrap().clone();
+            trace::insert(
+                resource.channel.clone(),
+                request_header(&resource.dbname, auth),
+                request.clone(),
+                None,
+                None,
+                headers.clone(),
+            )
+        }));
+    Ok((result.map(|_| ()), attempts))
+}
+
 #[rustler::nif(schedule = "DirtyIo")]
 fn stream_close(env: Env, resource: ResourceArc<StreamWriterResource>) -> NifResult<Term> {
     let runtime = &resource.runtime;
     let result: Result<(), String> = runtime.block_on(async {
         let mut writer_guard = resource.writer.lock().await;
         if let Some(writer_wrapper) = writer_guard.take() {
+            resource
+                .connection_metrics
+                .streams_open
+                .fetch_sub(1, Ordering::Relaxed);
             let writer = writer_wrapper.0;
             writer.finish().await.map_err(|e| e.to_string())?;
             Ok(())
@@ -476,4 +2160,120 @@ fn stream_close(env: Env, resource: ResourceArc<StreamWriterResource>) -> NifRes
     }
 }
 
+/// Decode `#{max_rows, max_bytes, linger, notify}`, `linger` in milliseconds.
+fn decode_buffer_options(opts: Term) -> Result<buffer::BufferOptions, String> {
+    let env = opts.get_env();
+    let get = |key: Atom| opts.map_get(key.to_term(env)).ok();
+    let invalid = |name: &str| format!("invalid {name} option");
+    let positive = |key: Atom, name: &str| -> Result<Option<usize>, String> {
+        match get(key).map(|t| t.decode::<usize>()) {
+            Some(Ok(n)) if n > 0 => Ok(Some(n)),
+            Some(_) => Err(invalid(name)),
+            None => Ok(None),
+        }
+    };
+
+    let linger = positive(atoms::linger(), "linger")?.map_or(buffer::DEFAULT_LINGER, |ms| {
+        Duration::from_millis(ms as u64)
+    });
+    let notify = get(atoms::notify())
+        .map(|t| t.decode::<rustler::LocalPid>())
+        .transpose()
+        .map_err(|_| invalid("notify"))?;
+    Ok(buffer::BufferOptions {
+        max_rows: positive(atoms::max_rows(), "max_rows")?.unwrap_or(buffer::DEFAULT_MAX_ROWS),
+        max_bytes: positive(atoms::max_bytes(), "max_bytes")?.unwrap_or(buffer::DEFAULT_MAX_BYTES),
+        linger,
+        notify,
+    })
+}
+
+/// A writer buffering rows per table on the connection, flushed from its runtime.
+#[rustler::nif]
+fn buffer_start<'a>(
+    env: Env<'a>,
+    resource: ResourceArc<GreptimeResource>,
+    opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let opts = match decode_buffer_options(opts) {
+        Ok(opts) => opts,
+        Err(err) => return Ok((atoms::error(), err).encode(env)),
+    };
+    let writer = ResourceArc::new(BufferedWriterResource {
+        buffer: buffer::Buffer::start(&resource, opts),
+        connection: resource,
+    });
+    Ok((atoms::ok(), writer).encode(env))
+}
+
+/// Convert the rows and add them to the buffer. The schema of a table is fetched on its
+/// first append only.
+#[rustler::nif(schedule = "DirtyIo")]
+fn append<'a>(
+    env: Env<'a>,
+    writer: ResourceArc<BufferedWriterResource>,
+    table: String,
+    rows_term: Vec<Term<'a>>,
+) -> NifResult<Term<'a>> {
+    if rows_term.is_empty() {
+        return Ok(atoms::ok().encode(env));
+    }
+    let resource = &writer.connection;
+    // Held until the rows are flushed
+    let Ok(permit) = resource.limiter.acquire(0, util::approx_size(&rows_term)) else {
+        return Ok((atoms::error(), atoms::overloaded()).encode(env));
+    };
+
+    let table_schema = match writer.buffer.schema(&table) {
+        Some(table_schema) => table_schema,
+        None => {
+            let table_schema = resource
+                .runtime
+                .block_on(resource.fetch_table_schema(
+                    &resource.database(),
+                    &resource.dbname,
+                    &table,
+                ))
+                .ok();
+            writer.buffer.cache_schema(&table, table_schema.clone());
+            table_schema
+        }
+    };
+    let (schema, rows) = convert_insert_rows(
+        resource,
+        table_schema.as_ref(),
+        rows_term,
+        &resource.convert_opts,
+        None,
+    )
+    .inspect_err(|_| {
+        resource
+            .metrics
+            .errors
+            .record(metrics::ErrorKind::Conversion)
+    })?;
+
+    match writer.buffer.append(table, schema, rows, permit) {
+        Ok(()) => Ok(atoms::ok().encode(env)),
+        Err(e) => Ok((atoms::error(), e).encode(env)),
+    }
+}
+
+/// Flush every table of the buffer without waiting for the results.
+#[rustler::nif]
+fn buffer_flush(env: Env, writer: ResourceArc<BufferedWriterResource>) -> NifResult<Term> {
+    if writer.buffer.flush() {
+        Ok(atoms::ok().encode(env))
+    } else {
+        Ok((atoms::error(), "writer is closed").encode(env))
+    }
+}
+
+/// Flush every table and wait until all flushes are done.
+#[rustler::nif(schedule = "DirtyIo")]
+fn buffer_close(env: Env, writer: ResourceArc<BufferedWriterResource>) -> NifResult<Term> {
+    writer.connection.runtime.block_on(writer.buffer.close());
+    Ok(atoms::ok().encode(env))
+}
+
 rustler::init!("greptimedb_rs_nif", load = load);
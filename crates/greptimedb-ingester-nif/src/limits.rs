@@ -0,0 +1,123 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use rustler::{Encoder, Env, Term};
+
+use crate::atoms;
+
+/// Bounds on what a connection or a stream has in flight, `None` is unbounded.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub max_requests: Option<u64>,
+    /// Size of the rows, as estimated by `util::approx_size`.
+    pub max_bytes: Option<u64>,
+    /// How long a call waits for room before failing with `overloaded`, zero fails at once.
+    pub timeout: Duration,
+}
+
+#[derive(Default)]
+struct Usage {
+    requests: u64,
+    bytes: u64,
+}
+
+/// The limits were reached and no room was released in time.
+#[derive(Debug)]
+pub struct Overloaded;
+
+pub struct Limiter {
+    limits: Limits,
+    usage: Mutex<Usage>,
+    released: Condvar,
+    /// Calls that failed with `overloaded`.
+    overloaded: AtomicU64,
+}
+
+impl Limiter {
+    pub fn new(limits: Limits) -> Self {
+        Limiter {
+            limits,
+            usage: Mutex::new(Usage::default()),
+            released: Condvar::new(),
+            overloaded: AtomicU64::new(0),
+        }
+    }
+
+    fn admits(&self, usage: &Usage, requests: u64, bytes: u64) -> bool {
+        // What exceeds the limits on its own still goes through when nothing else is in flight
+        if usage.requests == 0 && usage.bytes == 0 {
+            return true;
+        }
+        let requests_fit = match self.limits.max_requests {
+            Some(max) => usage.requests + requests <= max,
+            None => true,
+        };
+        let bytes_fit = match self.limits.max_bytes {
+            Some(max) => usage.bytes + bytes <= max,
+            None => true,
+        };
+        requests_fit && bytes_fit
+    }
+
+    /// Room for `requests` carrying `bytes`, blocking the caller up to the timeout.
+    pub fn acquire(self: &Arc<Self>, requests: u64, bytes: u64) -> Result<Permit, Overloaded> {
+        let usage = self.usage.lock().unwrap();
+        let (mut usage, _) = self
+            .released
+            .wait_timeout_while(usage, self.limits.timeout, |usage| {
+                !self.admits(usage, requests, bytes)
+            })
+            .unwrap();
+        if !self.admits(&usage, requests, bytes) {
+            self.overloaded.fetch_add(1, Ordering::Relaxed);
+            return Err(Overloaded);
+        }
+        usage.requests += requests;
+        usage.bytes += bytes;
+        Ok(Permit {
+            limiter: self.clone(),
+            requests,
+            bytes,
+        })
+    }
+
+    /// `in_flight_bytes` and `overloaded`, for the stats.
+    pub fn pairs<'a>(&self, env: Env<'a>) -> Vec<(Term<'a>, Term<'a>)> {
+        let bytes = self.usage.lock().unwrap().bytes;
+        vec![
+            (atoms::in_flight_bytes().encode(env), bytes.encode(env)),
+            (
+                atoms::overloaded().encode(env),
+                self.overloaded.load(Ordering::Relaxed).encode(env),
+            ),
+        ]
+    }
+}
+
+/// Room taken in a limiter, given back when dropped.
+pub struct Permit {
+    limiter: Arc<Limiter>,
+    requests: u64,
+    bytes: u64,
+}
+
+impl Permit {
+    /// Hold the room of `other` too, both must come from the same limiter.
+    pub fn merge(&mut self, mut other: Permit) {
+        self.requests += std::mem::take(&mut other.requests);
+        self.bytes += std::mem::take(&mut other.bytes);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.requests == 0 && self.bytes == 0 {
+            return;
+        }
+        let mut usage = self.limiter.usage.lock().unwrap();
+        usage.requests -= self.requests;
+        usage.bytes -= self.bytes;
+        self.limiter.released.notify_all();
+    }
+}
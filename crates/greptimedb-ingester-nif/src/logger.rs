@@ -0,0 +1,188 @@
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use rustler::{Encoder, Env, LocalPid, OwnedEnv, Term};
+
+use crate::atoms;
+
+/// Records queued for the handler before new ones are dropped.
+const QUEUE_SIZE: usize = 1024;
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::Warn;
+pub const DEFAULT_MAX_PER_SECOND: u32 = 100;
+
+static LOGGER: ErlangLogger = ErlangLogger;
+static STATE: Mutex<State> = Mutex::new(State {
+    handler: None,
+    max_per_second: DEFAULT_MAX_PER_SECOND,
+    window: 0,
+    in_window: 0,
+    dropped: 0,
+});
+static QUEUE: OnceLock<SyncSender<Entry>> = OnceLock::new();
+
+struct State {
+    handler: Option<LocalPid>,
+    max_per_second: u32,
+    /// The second being counted, since the epoch.
+    window: u64,
+    in_window: u32,
+    /// Over the rate limit or the queue size, reported when the next window starts.
+    dropped: u64,
+}
+
+/// A record on its way to the handler, sent as
+/// `{greptimedb_rs_log, Level, Message, #{target, module_path, file, line}}`.
+struct Entry {
+    handler: LocalPid,
+    level: Level,
+    message: String,
+    target: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+}
+
+impl Entry {
+    fn dropped(handler: LocalPid, dropped: u64) -> Self {
+        Entry {
+            handler,
+            level: Level::Warn,
+            message: format!("{dropped} log messages dropped by the rate limit"),
+            target: module_path!().to_string(),
+            module_path: None,
+            file: None,
+            line: None,
+        }
+    }
+}
+
+impl Encoder for Entry {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let level = match self.level {
+            Level::Error => atoms::error(),
+            Level::Warn => atoms::warning(),
+            Level::Info => atoms::info(),
+            // The Erlang logger has no trace level
+            Level::Debug | Level::Trace => atoms::debug(),
+        };
+        let undefined = rustler::types::atom::undefined().encode(env);
+        let meta = Term::map_from_pairs(
+            env,
+            &[
+                (atoms::target().encode(env), self.target.encode(env)),
+                (
+                    atoms::module_path().encode(env),
+                    self.module_path
+                        .as_ref()
+                        .map_or(undefined, |m| m.encode(env)),
+                ),
+                (
+                    atoms::file().encode(env),
+                    self.file.as_ref().map_or(undefined, |f| f.encode(env)),
+                ),
+                (
+                    atoms::line().encode(env),
+                    self.line.map_or(undefined, |l| l.encode(env)),
+                ),
+            ],
+        )
+        .expect("unique keys");
+        (
+            atoms::greptimedb_rs_log(),
+            level,
+            self.message.as_str(),
+            meta,
+        )
+            .encode(env)
+    }
+}
+
+/// Forwards `log` records of the ingester and its dependencies to an Erlang process.
+struct ErlangLogger;
+
+impl Log for ErlangLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let Some(queue) = QUEUE.get() else {
+            return;
+        };
+
+        let mut state = STATE.lock().unwrap();
+        let Some(handler) = state.handler else {
+            return;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if now != state.window {
+            state.window = now;
+            state.in_window = 0;
+            if state.dropped > 0 {
+                let _ = queue.try_send(Entry::dropped(handler, state.dropped));
+                state.dropped = 0;
+            }
+        }
+        if state.in_window >= state.max_per_second {
+            state.dropped += 1;
+            return;
+        }
+        state.in_window += 1;
+
+        let entry = Entry {
+            handler,
+            level: record.level(),
+            message: record.args().to_string(),
+            target: record.target().to_string(),
+            module_path: record.module_path().map(str::to_string),
+            file: record.file().map(str::to_string),
+            line: record.line(),
+        };
+        if let Err(TrySendError::Full(_)) = queue.try_send(entry) {
+            state.dropped += 1;
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install the logger, records are dropped until a handler is set.
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(DEFAULT_LEVEL);
+    }
+}
+
+/// Send the records to `handler`, or stop forwarding them with `None`.
+///
+/// Messages are sent from a dedicated thread, since records are also emitted on scheduler
+/// threads where sending from an owned environment is not allowed.
+pub fn set_handler(handler: Option<LocalPid>, max_per_second: u32) {
+    QUEUE.get_or_init(|| {
+        let (sender, receiver) = mpsc::sync_channel::<Entry>(QUEUE_SIZE);
+        std::thread::Builder::new()
+            .name("greptimedb_rs_log".to_string())
+            .spawn(move || {
+                let mut env = OwnedEnv::new();
+                for entry in receiver {
+                    // The handler may be gone, nothing to report it to
+                    let _ = env.send_and_clear(&entry.handler, |env| entry.encode(env));
+                }
+            })
+            .expect("failed to spawn the log forwarding thread");
+        sender
+    });
+
+    let mut state = STATE.lock().unwrap();
+    state.handler = handler;
+    state.max_per_second = max_per_second;
+    state.dropped = 0;
+}
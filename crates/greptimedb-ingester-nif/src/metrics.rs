@@ -0,0 +1,270 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use rustler::{Encoder, Env, Term};
+
+use crate::atoms;
+use crate::retry::Failure;
+
+/// Upper bounds of the latency buckets in microseconds, the last bucket is unbounded.
+const LATENCY_BUCKETS_US: [u64; 14] = [
+    500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000,
+    2_500_000, 5_000_000, 10_000_000,
+];
+
+/// Latency distribution, encoded as `#{buckets => [{Le, Count}], count => N, sum => Us}`
+/// with cumulative counts as Prometheus expects them.
+#[derive(Default)]
+pub struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_US.len() + 1],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        let bucket = LATENCY_BUCKETS_US
+            .iter()
+            .position(|&le| us <= le)
+            .unwrap_or(LATENCY_BUCKETS_US.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+    }
+}
+
+impl Encoder for Histogram {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let mut cumulative = 0;
+        let buckets: Vec<Term> = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                let le = match LATENCY_BUCKETS_US.get(i) {
+                    Some(le) => le.encode(env),
+                    None => atoms::infinity().encode(env),
+                };
+                (le, cumulative).encode(env)
+            })
+            .collect();
+        Term::map_from_pairs(
+            env,
+            &[
+                (atoms::buckets().encode(env), buckets.encode(env)),
+                (atoms::count().encode(env), counter(&self.count).encode(env)),
+                (atoms::sum().encode(env), counter(&self.sum_us).encode(env)),
+            ],
+        )
+        .expect("unique keys")
+    }
+}
+
+/// Why a request failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Rows that could not be converted to the table schema.
+    Conversion,
+    Timeout,
+    Unavailable,
+    /// Anything else reported by the server.
+    Server,
+}
+
+impl ErrorKind {
+    pub fn classify(err: &Failure) -> Self {
+        match err.code {
+            Some(tonic::Code::DeadlineExceeded) => ErrorKind::Timeout,
+            Some(tonic::Code::Unavailable) => ErrorKind::Unavailable,
+            _ => ErrorKind::Server,
+        }
+    }
+}
+
+/// Failures by kind, encoded as `#{conversion => N, timeout => N, ...}`.
+#[derive(Default)]
+pub struct ErrorCounters {
+    conversion: AtomicU64,
+    timeout: AtomicU64,
+    unavailable: AtomicU64,
+    server: AtomicU64,
+}
+
+impl ErrorCounters {
+    pub fn record(&self, kind: ErrorKind) {
+        let counter = match kind {
+            ErrorKind::Conversion => &self.conversion,
+            ErrorKind::Timeout => &self.timeout,
+            ErrorKind::Unavailable => &self.unavailable,
+            ErrorKind::Server => &self.server,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_err(&self, err: &Failure) {
+        self.record(ErrorKind::classify(err));
+    }
+}
+
+impl Encoder for ErrorCounters {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        Term::map_from_pairs(
+            env,
+            &[
+                (atoms::conversion(), counter(&self.conversion)),
+                (atoms::timeout(), counter(&self.timeout)),
+                (atoms::unavailable(), counter(&self.unavailable)),
+                (atoms::server(), counter(&self.server)),
+            ]
+            .map(|(key, value)| (key.encode(env), value.encode(env))),
+        )
+        .expect("unique keys")
+    }
+}
+
+/// Decrements the gauge it was taken from when dropped.
+pub struct Gauge<'a>(&'a AtomicU64);
+
+impl<'a> Gauge<'a> {
+    pub fn inc(gauge: &'a AtomicU64) -> Self {
+        gauge.fetch_add(1, Ordering::Relaxed);
+        Gauge(gauge)
+    }
+}
+
+impl Drop for Gauge<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Counters of a connection, for everything but its streams.
+#[derive(Default)]
+pub struct ConnectionMetrics {
+    pub inserts: AtomicU64,
+    pub queries: AtomicU64,
+    pub deletes: AtomicU64,
+    pub rows_written: AtomicU64,
+    /// Left out of a `partial_write` insert.
+    pub rows_rejected: AtomicU64,
+    /// Encoded size of the insert requests, before compression, counting every attempt.
+    pub bytes_sent: AtomicU64,
+    pub retries: AtomicU64,
+    pub schema_fetches: AtomicU64,
+    pub errors: ErrorCounters,
+    pub in_flight: AtomicU64,
+    pub streams_open: AtomicU64,
+    pub insert_latency: Histogram,
+    pub query_latency: Histogram,
+    pub delete_latency: Histogram,
+    pub schema_fetch_latency: Histogram,
+}
+
+impl ConnectionMetrics {
+    pub fn pairs<'a>(&self, env: Env<'a>) -> Vec<(Term<'a>, Term<'a>)> {
+        vec![
+            (
+                atoms::inserts().encode(env),
+                counter(&self.inserts).encode(env),
+            ),
+            (
+                atoms::queries().encode(env),
+                counter(&self.queries).encode(env),
+            ),
+            (
+                atoms::deletes().encode(env),
+                counter(&self.deletes).encode(env),
+            ),
+            (
+                atoms::rows_written().encode(env),
+                counter(&self.rows_written).encode(env),
+            ),
+            (
+                atoms::rows_rejected().encode(env),
+                counter(&self.rows_rejected).encode(env),
+            ),
+            (
+                atoms::bytes_sent().encode(env),
+                counter(&self.bytes_sent).encode(env),
+            ),
+            (
+                atoms::retries().encode(env),
+                counter(&self.retries).encode(env),
+            ),
+            (
+                atoms::schema_fetches().encode(env),
+                counter(&self.schema_fetches).encode(env),
+            ),
+            (atoms::errors().encode(env), self.errors.encode(env)),
+            (
+                atoms::in_flight().encode(env),
+                counter(&self.in_flight).encode(env),
+            ),
+            (
+                atoms::streams_open().encode(env),
+                counter(&self.streams_open).encode(env),
+            ),
+            (
+                atoms::insert_latency_us().encode(env),
+                self.insert_latency.encode(env),
+            ),
+            (
+                atoms::query_latency_us().encode(env),
+                self.query_latency.encode(env),
+            ),
+            (
+                atoms::delete_latency_us().encode(env),
+                self.delete_latency.encode(env),
+            ),
+            (
+                atoms::schema_fetch_latency_us().encode(env),
+                self.schema_fetch_latency.encode(env),
+            ),
+        ]
+    }
+}
+
+/// Counters of a stream.
+#[derive(Default)]
+pub struct StreamMetrics {
+    pub writes: AtomicU64,
+    pub rows_written: AtomicU64,
+    pub retries: AtomicU64,
+    pub errors: ErrorCounters,
+    pub in_flight: AtomicU64,
+    pub write_latency: Histogram,
+}
+
+impl StreamMetrics {
+    pub fn pairs<'a>(&self, env: Env<'a>) -> Vec<(Term<'a>, Term<'a>)> {
+        vec![
+            (
+                atoms::writes().encode(env),
+                counter(&self.writes).encode(env),
+            ),
+            (
+                atoms::rows_written().encode(env),
+                counter(&self.rows_written).encode(env),
+            ),
+            (
+                atoms::retries().encode(env),
+                counter(&self.retries).encode(env),
+            ),
+            (atoms::errors().encode(env), self.errors.encode(env)),
+            (
+                atoms::in_flight().encode(env),
+                counter(&self.in_flight).encode(env),
+            ),
+            (
+                atoms::write_latency_us().encode(env),
+                self.write_latency.encode(env),
+            ),
+        ]
+    }
+}
+
+fn counter(value: &AtomicU64) -> u64 {
+    value.load(Ordering::Relaxed)
+}
@@ -0,0 +1,171 @@
+use std::io;
+
+use base64::Engine;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyKind {
+    /// HTTP CONNECT
+    Http,
+    Socks5,
+}
+
+#[derive(Debug)]
+pub struct ProxyConfig {
+    pub kind: ProxyKind,
+    /// `host:port` of the proxy.
+    pub addr: String,
+    pub auth: Option<(String, String)>,
+    /// Hosts reached directly: exact names, `.domain` suffixes, or `*` for all.
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Parse `http://host:port` or `socks5://host:port`. The port defaults to 3128 and 1080.
+    pub fn parse_url(url: &str) -> Result<(ProxyKind, String), String> {
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or_else(|| format!("invalid proxy url `{url}`"))?;
+        let (kind, default_port) = match scheme {
+            "http" => (ProxyKind::Http, 3128),
+            "socks5" | "socks5h" => (ProxyKind::Socks5, 1080),
+            _ => return Err(format!("unsupported proxy scheme `{scheme}`")),
+        };
+        let authority = rest.trim_end_matches('/');
+        if authority.is_empty() || authority.contains(['/', '@']) {
+            return Err(format!("invalid proxy url `{url}`"));
+        }
+        let addr = match split_host_port(authority) {
+            Some(_) => authority.to_string(),
+            None => format!("{authority}:{default_port}"),
+        };
+        Ok((kind, addr))
+    }
+
+    pub fn bypass(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.no_proxy.iter().any(|pattern| {
+            let pattern = pattern.to_ascii_lowercase();
+            pattern == "*"
+                || host == pattern
+                || (pattern.starts_with('.') && host.ends_with(&pattern))
+                || host.ends_with(&format!(".{pattern}"))
+        })
+    }
+
+    /// Open a stream to `host:port` through the proxy.
+    pub async fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let mut stream = TcpStream::connect(&self.addr).await?;
+        stream.set_nodelay(true)?;
+        match self.kind {
+            ProxyKind::Http => self.http_connect(&mut stream, host, port).await?,
+            ProxyKind::Socks5 => self.socks5_connect(&mut stream, host, port).await?,
+        }
+        Ok(stream)
+    }
+
+    async fn http_connect(&self, stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+        let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+        if let Some((username, password)) = &self.auth {
+            let credentials =
+                base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+            request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // Read the response head byte by byte, so nothing of the tunnel is consumed
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() > 8192 {
+                return Err(proxy_error("response head too large"));
+            }
+            head.push(stream.read_u8().await?);
+        }
+        let status_line = String::from_utf8_lossy(&head);
+        let status_line = status_line.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(proxy_error(&format!("CONNECT refused: {status_line}"))),
+        }
+    }
+
+    async fn socks5_connect(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> io::Result<()> {
+        // Greeting: no authentication, or username/password when configured
+        let methods: &[u8] = if self.auth.is_some() {
+            &[0x00, 0x02]
+        } else {
+            &[0x00]
+        };
+        let mut greeting = vec![0x05, socks5_len("methods", methods)?];
+        greeting.extend_from_slice(methods);
+        stream.write_all(&greeting).await?;
+
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).await?;
+        match (choice, &self.auth) {
+            ([0x05, 0x00], _) => {}
+            ([0x05, 0x02], Some((username, password))) => {
+                let mut auth = vec![0x01, socks5_len("username", username.as_bytes())?];
+                auth.extend_from_slice(username.as_bytes());
+                auth.push(socks5_len("password", password.as_bytes())?);
+                auth.extend_from_slice(password.as_bytes());
+                stream.write_all(&auth).await?;
+
+                let mut status = [0u8; 2];
+                stream.read_exact(&mut status).await?;
+                if status[1] != 0x00 {
+                    return Err(proxy_error("SOCKS5 authentication failed"));
+                }
+            }
+            _ => return Err(proxy_error("SOCKS5 proxy accepts no offered method")),
+        }
+
+        // CONNECT by domain name, resolved by the proxy
+        let mut request = vec![0x05, 0x01, 0x00, 0x03, socks5_len("host", host.as_bytes())?];
+        request.extend_from_slice(host.as_bytes());
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0x00 {
+            return Err(proxy_error(&format!(
+                "SOCKS5 CONNECT refused ({})",
+                reply[1]
+            )));
+        }
+        let bound_addr_len = match reply[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => stream.read_u8().await? as usize,
+            _ => return Err(proxy_error("invalid SOCKS5 reply")),
+        };
+        let mut bound = vec![0u8; bound_addr_len + 2];
+        stream.read_exact(&mut bound).await?;
+        Ok(())
+    }
+}
+
+/// SOCKS5 prefixes fields with a one byte length.
+fn socks5_len(field: &str, bytes: &[u8]) -> io::Result<u8> {
+    u8::try_from(bytes.len()).map_err(|_| proxy_error(&format!("SOCKS5 {field} over 255 bytes")))
+}
+
+fn proxy_error(reason: &str) -> io::Error {
+    io::Error::other(format!("proxy: {reason}"))
+}
+
+/// Split `host:port`, with `[v6]:port` support.
+pub fn split_host_port(addr: &str) -> Option<(&str, u16)> {
+    let (host, port) = addr.rsplit_once(':')?;
+    let port = port.parse().ok()?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Some((host, port))
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use rustler::{Encoder, Env, Term};
+use tonic::Code;
+
+/// gRPC status codes a failed write may be retried on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryCode {
+    Unavailable,
+    DeadlineExceeded,
+    ResourceExhausted,
+    Aborted,
+    Cancelled,
+    Internal,
+    Unknown,
+}
+
+impl RetryCode {
+    fn code(self) -> Code {
+        match self {
+            RetryCode::Unavailable => Code::Unavailable,
+            RetryCode::DeadlineExceeded => Code::DeadlineExceeded,
+            RetryCode::ResourceExhausted => Code::ResourceExhausted,
+            RetryCode::Aborted => Code::Aborted,
+            RetryCode::Cancelled => Code::Cancelled,
+            RetryCode::Internal => Code::Internal,
+            RetryCode::Unknown => Code::Unknown,
+        }
+    }
+}
+
+/// A failed call, with the gRPC status code it failed with. Failures that never reached
+/// the server as a call, such as a request that could not be built, have no code and are
+/// never retried.
+#[derive(Debug)]
+pub struct Failure {
+    pub code: Option<Code>,
+    pub message: String,
+}
+
+impl Failure {
+    /// The code of the first status or transport error in the source chain of `err`.
+    fn code_of(err: &(dyn std::error::Error + 'static)) -> Option<Code> {
+        let mut source = Some(err);
+        while let Some(err) = source {
+            if let Some(status) = err.downcast_ref::<tonic::Status>() {
+                return Some(status.code());
+            }
+            if err.is::<tonic::transport::Error>() {
+                return Some(Code::Unavailable);
+            }
+            source = err.source();
+        }
+        None
+    }
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure {
+            code: None,
+            message,
+        }
+    }
+}
+
+impl From<tonic::Status> for Failure {
+    fn from(status: tonic::Status) -> Self {
+        Failure {
+            code: Some(status.code()),
+            message: status.to_string(),
+        }
+    }
+}
+
+impl From<greptimedb_ingester::Error> for Failure {
+    fn from(err: greptimedb_ingester::Error) -> Self {
+        let code = match &err {
+            greptimedb_ingester::Error::Server { status, .. } => Some(status.code()),
+            err => Failure::code_of(err),
+        };
+        Failure {
+            code,
+            message: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Encoder for Failure {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        self.message.encode(env)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Including the first attempt.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Fraction of each backoff that is randomized, in `0.0..=1.0`.
+    pub jitter: f64,
+    pub retry_on: Vec<RetryCode>,
+    /// Budget for all attempts and backoffs together.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.2,
+            // Not `DeadlineExceeded`: the write may have been applied, and retrying it would
+            // duplicate the rows of an append-only table.
+            retry_on: vec![RetryCode::Unavailable, RetryCode::ResourceExhausted],
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before the next attempt, or `None` when `err` after
+    /// `attempts` attempts is final.
+    pub fn next_backoff(&self, attempts: u32, started: Instant, err: &Failure) -> Option<Duration> {
+        let retryable = err
+            .code
+            .is_some_and(|code| self.retry_on.iter().any(|retry| retry.code() == code));
+        if attempts >= self.max_attempts || !retryable {
+            return None;
+        }
+
+        let exp = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempts - 1));
+        let backoff = exp.min(self.max_backoff);
+        let backoff = if self.jitter > 0.0 {
+            let factor = 1.0 - self.jitter * rand::thread_rng().gen::<f64>();
+            backoff.mul_f64(factor)
+        } else {
+            backoff
+        };
+
+        match self.deadline {
+            Some(deadline) if started.elapsed() + backoff >= deadline => None,
+            _ => Some(backoff),
+        }
+    }
+}
+
+/// Run `attempt` until it succeeds or `policy` gives up, sleeping on the runtime in between.
+/// Without a policy the operation is attempted once. Returns the last result and the number
+/// of attempts made.
+pub async fn run<T, F, Fut>(policy: Option<&RetryPolicy>, attempt: F) -> (Result<T, Failure>, u32)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Failure>>,
+{
+    run_with(policy, attempt, true).await
+}
+
+/// Like [`run`], for attempts that must not be cancelled half way, such as a write on a
+/// stream: the deadline is only checked before starting another attempt.
+pub async fn run_to_completion<T, F, Fut>(
+    policy: Option<&RetryPolicy>,
+    attempt: F,
+) -> (Result<T, Failure>, u32)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Failure>>,
+{
+    run_with(policy, attempt, false).await
+}
+
+async fn run_with<T, F, Fut>(
+    policy: Option<&RetryPolicy>,
+    mut attempt: F,
+    cancellable: bool,
+) -> (Result<T, Failure>, u32)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Failure>>,
+{
+    let started = Instant::now();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let remaining = policy
+            .and_then(|p| p.deadline)
+            .filter(|_| cancellable)
+            .map(|deadline| deadline.saturating_sub(started.elapsed()));
+        let result = match remaining {
+            Some(remaining) => tokio::time::timeout(remaining, attempt())
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Failure {
+                        code: Some(Code::DeadlineExceeded),
+                        message: "retry deadline exceeded".to_string(),
+                    })
+                }),
+            None => attempt().await,
+        };
+
+        let err = match result {
+            Ok(value) => return (Ok(value), attempts),
+            Err(err) => err,
+        };
+        match policy.and_then(|p| p.next_backoff(attempts, started, &err)) {
+            Some(backoff) => tokio::time::sleep(backoff).await,
+            None => return (Err(err), attempts),
+        }
+    }
+}
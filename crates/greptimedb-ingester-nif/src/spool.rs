@@ -0,0 +1,512 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use greptimedb_ingester::api::v1::RowInsertRequests;
+use prost::Message;
+use rustler::{Encoder, Env, Term};
+use tokio::runtime::Runtime;
+
+use crate::metrics::ErrorKind;
+use crate::retry::Failure;
+use crate::{atoms, health, SharedAuth};
+
+pub const DEFAULT_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+pub const DEFAULT_SEGMENT_BYTES: u64 = 16 * 1024 * 1024;
+pub const DEFAULT_REPLAY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Length and CRC32 of the payload, little endian.
+const HEADER_LEN: u64 = 8;
+const CURSOR_FILE: &str = "cursor";
+
+/// What makes room when the spool is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eviction {
+    /// Delete the oldest segment.
+    DropOldest,
+    /// Refuse the new request.
+    RejectNew,
+}
+
+#[derive(Clone, Debug)]
+pub struct SpoolOptions {
+    pub dir: PathBuf,
+    /// Size of all the segments together.
+    pub max_bytes: u64,
+    /// A new segment is started once the current one would grow past this size.
+    pub segment_bytes: u64,
+    pub eviction: Eviction,
+    pub replay_interval: Duration,
+}
+
+/// How a write ended: sent, or kept in the spool to be replayed.
+#[derive(Clone, Copy, Debug)]
+pub enum Written {
+    Affected(u32),
+    Spooled,
+}
+
+impl Encoder for Written {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            Written::Affected(affected) => affected.encode(env),
+            Written::Spooled => atoms::spooled().encode(env),
+        }
+    }
+}
+
+/// Failures the server may recover from, whose requests are worth keeping.
+pub fn recoverable(err: &Failure) -> bool {
+    matches!(
+        ErrorKind::classify(err),
+        ErrorKind::Timeout | ErrorKind::Unavailable
+    )
+}
+
+/// A file of records, `records` of them not replayed yet.
+struct Segment {
+    seq: u64,
+    bytes: u64,
+    records: u64,
+}
+
+/// Where replay resumes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    seq: u64,
+    offset: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    spooled: u64,
+    replayed: u64,
+    /// Deleted by `drop_oldest` before being replayed.
+    evicted: u64,
+    /// Rejected by the server on replay.
+    discarded: u64,
+    /// Records that failed their checksum or didn't decode, with what followed them.
+    corrupt: u64,
+}
+
+struct State {
+    /// Oldest first, appends go to the last one.
+    segments: VecDeque<Segment>,
+    writer: File,
+    cursor: Position,
+    bytes: u64,
+    counters: Counters,
+}
+
+/// Insert requests kept on disk while the server is unreachable, replayed in order.
+pub struct Spool {
+    opts: SpoolOptions,
+    state: Mutex<State>,
+    /// Held by the task replaying, connections sharing the spool take turns.
+    replaying: tokio::sync::Mutex<()>,
+}
+
+/// Spools by directory, so that a connection re-established while the previous one is
+/// still around shares its spool instead of writing the same files.
+static OPEN: Mutex<Vec<(PathBuf, Weak<Spool>)>> = Mutex::new(Vec::new());
+
+impl Spool {
+    /// The spool of `opts.dir`, picking up the segments left by a previous run. A spool
+    /// already open keeps its options.
+    pub fn open(opts: SpoolOptions) -> Result<Arc<Self>, String> {
+        let mut open = OPEN.lock().unwrap();
+        open.retain(|(_, spool)| spool.strong_count() > 0);
+        if let Some(spool) = open
+            .iter()
+            .find(|(dir, _)| *dir == opts.dir)
+            .and_then(|(_, spool)| spool.upgrade())
+        {
+            return Ok(spool);
+        }
+
+        let dir = opts.dir.clone();
+        let spool =
+            Arc::new(Spool::load(opts).map_err(|e| format!("spool `{}`: {e}", dir.display()))?);
+        open.push((dir, Arc::downgrade(&spool)));
+        Ok(spool)
+    }
+
+    fn load(opts: SpoolOptions) -> io::Result<Self> {
+        fs::create_dir_all(&opts.dir)?;
+        let mut seqs: Vec<u64> = fs::read_dir(&opts.dir)?
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name();
+                name.to_str()?.strip_suffix(".seg")?.parse().ok()
+            })
+            .collect();
+        seqs.sort_unstable();
+        let cursor = read_cursor(&opts.dir)?.unwrap_or(Position {
+            seq: seqs.first().copied().unwrap_or(0),
+            offset: 0,
+        });
+
+        let mut segments = VecDeque::with_capacity(seqs.len() + 1);
+        let mut bytes = 0;
+        let mut counters = Counters::default();
+        for seq in seqs {
+            let path = segment_path(&opts.dir, seq);
+            if seq < cursor.seq {
+                // Replayed already
+                fs::remove_file(&path)?;
+                continue;
+            }
+            let len = fs::metadata(&path)?.len();
+            let from = if seq == cursor.seq { cursor.offset } else { 0 };
+            let (records, end) = count_records(&path, from)?;
+            if end < len {
+                counters.corrupt += 1;
+            }
+            bytes += len;
+            segments.push_back(Segment {
+                seq,
+                bytes: len,
+                records,
+            });
+        }
+
+        // Appends start a new segment, so a write torn by a crash stays at the end of a
+        // segment that is only read
+        let seq = segments
+            .back()
+            .map_or(cursor.seq, |segment| segment.seq)
+            .max(cursor.seq)
+            + 1;
+        let writer = open_segment(&opts.dir, seq)?;
+        segments.push_back(Segment {
+            seq,
+            bytes: 0,
+            records: 0,
+        });
+
+        Ok(Spool {
+            opts,
+            state: Mutex::new(State {
+                segments,
+                writer,
+                cursor,
+                bytes,
+                counters,
+            }),
+            replaying: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Requests waiting to be replayed.
+    pub fn pending(&self) -> u64 {
+        let state = self.state.lock().unwrap();
+        state.segments.iter().map(|segment| segment.records).sum()
+    }
+
+    /// Append a request, evicting the oldest segments when full with `drop_oldest`.
+    pub fn push(&self, request: &RowInsertRequests) -> Result<(), String> {
+        let payload = request.encode_to_vec();
+        let len = HEADER_LEN + payload.len() as u64;
+        if len > self.opts.max_bytes {
+            return Err("request is larger than the spool".to_string());
+        }
+
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        let active = state.segments.back().expect("active segment");
+        if active.bytes > 0 && active.bytes + len > self.opts.segment_bytes {
+            let seq = active.seq + 1;
+            state.writer = open_segment(&self.opts.dir, seq).map_err(|e| e.to_string())?;
+            state.segments.push_back(Segment {
+                seq,
+                bytes: 0,
+                records: 0,
+            });
+        }
+        while state.bytes + len > self.opts.max_bytes {
+            if self.opts.eviction == Eviction::RejectNew || state.segments.len() == 1 {
+                return Err("spool is full".to_string());
+            }
+            let evicted = self.drop_front(state);
+            state.counters.evicted += evicted;
+        }
+
+        let mut record = Vec::with_capacity(len as usize);
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&checksum(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+        state.writer.write_all(&record).map_err(|e| e.to_string())?;
+        state.writer.sync_data().map_err(|e| e.to_string())?;
+
+        let active = state.segments.back_mut().expect("active segment");
+        active.bytes += len;
+        active.records += 1;
+        state.bytes += len;
+        state.counters.spooled += 1;
+        Ok(())
+    }
+
+    /// The oldest request not replayed yet, with the position following it.
+    fn peek(&self) -> Option<(RowInsertRequests, Position)> {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        loop {
+            let front = state.segments.front()?;
+            let sealed = state.segments.len() > 1;
+            if state.cursor.seq != front.seq {
+                state.cursor = Position {
+                    seq: front.seq,
+                    offset: 0,
+                };
+            }
+            if front.records == 0 {
+                if !sealed {
+                    return None;
+                }
+                self.drop_front(state);
+                continue;
+            }
+
+            let cursor = state.cursor;
+            let payload = read_record_at(&segment_path(&self.opts.dir, cursor.seq), cursor.offset);
+            match payload {
+                Ok(Some(payload)) => {
+                    let next = Position {
+                        seq: cursor.seq,
+                        offset: cursor.offset + HEADER_LEN + payload.len() as u64,
+                    };
+                    match RowInsertRequests::decode(payload.as_slice()) {
+                        Ok(request) => return Some((request, next)),
+                        Err(_) => {
+                            state.counters.corrupt += 1;
+                            self.advance(state, next);
+                        }
+                    }
+                }
+                // Counted when the segment was loaded, or unreadable
+                _ if sealed => {
+                    let lost = self.drop_front(state);
+                    state.counters.corrupt += lost;
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// The request before `next` was written.
+    fn commit(&self, next: Position) {
+        let mut state = self.state.lock().unwrap();
+        state.counters.replayed += 1;
+        self.advance(&mut state, next);
+    }
+
+    /// The request before `next` was rejected by the server.
+    fn discard(&self, next: Position) {
+        let mut state = self.state.lock().unwrap();
+        state.counters.discarded += 1;
+        self.advance(&mut state, next);
+    }
+
+    fn advance(&self, state: &mut State, next: Position) {
+        // Evicted meanwhile
+        if state.segments.front().map(|segment| segment.seq) != Some(next.seq) {
+            return;
+        }
+        state.cursor = next;
+        if let Some(front) = state.segments.front_mut() {
+            front.records = front.records.saturating_sub(1);
+        }
+        if let Err(e) = write_cursor(&self.opts.dir, next) {
+            log::warn!("failed to save the spool cursor: {e}");
+        }
+    }
+
+    /// Delete the oldest segment, returning how many of its records were not replayed.
+    fn drop_front(&self, state: &mut State) -> u64 {
+        let Some(segment) = state.segments.pop_front() else {
+            return 0;
+        };
+        state.bytes -= segment.bytes;
+        if let Err(e) = fs::remove_file(segment_path(&self.opts.dir, segment.seq)) {
+            log::warn!("failed to delete spool segment {}: {e}", segment.seq);
+        }
+        if let Some(front) = state.segments.front() {
+            state.cursor = Position {
+                seq: front.seq,
+                offset: 0,
+            };
+            if let Err(e) = write_cursor(&self.opts.dir, state.cursor) {
+                log::warn!("failed to save the spool cursor: {e}");
+            }
+        }
+        segment.records
+    }
+
+    /// `#{dir, records, bytes, segments, spooled, replayed, evicted, discarded, corrupt}`.
+    pub fn status<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let state = self.state.lock().unwrap();
+        let records: u64 = state.segments.iter().map(|segment| segment.records).sum();
+        let counters = &state.counters;
+        Term::map_from_pairs(
+            env,
+            &[
+                (
+                    atoms::dir().encode(env),
+                    self.opts.dir.to_string_lossy().as_ref().encode(env),
+                ),
+                (atoms::records().encode(env), records.encode(env)),
+                (atoms::bytes().encode(env), state.bytes.encode(env)),
+                (
+                    atoms::segments().encode(env),
+                    state.segments.len().encode(env),
+                ),
+                (atoms::spooled().encode(env), counters.spooled.encode(env)),
+                (atoms::replayed().encode(env), counters.replayed.encode(env)),
+                (atoms::evicted().encode(env), counters.evicted.encode(env)),
+                (
+                    atoms::discarded().encode(env),
+                    counters.discarded.encode(env),
+                ),
+                (atoms::corrupt().encode(env), counters.corrupt.encode(env)),
+            ],
+        )
+        .expect("unique keys")
+    }
+}
+
+/// Replay the spool through the connection's endpoints, once they are reachable again.
+///
+/// A request the server rejects for another reason than being unavailable is discarded,
+/// it would block the ones behind it forever. The task stops once the endpoints are
+/// dropped with their connection.
+pub fn spawn_replay(
+    spool: &Arc<Spool>,
+    endpoints: &Arc<health::Endpoints>,
+    runtime: &Runtime,
+    dbname: String,
+    auth: SharedAuth,
+    ttl: Option<String>,
+) {
+    let spool = spool.clone();
+    let weak = Arc::downgrade(endpoints);
+    runtime.spawn(async move {
+        let mut ticker = tokio::time::interval(spool.opts.replay_interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let Some(endpoints) = weak.upgrade() else {
+                break;
+            };
+            let Ok(_replaying) = spool.replaying.try_lock() else {
+                continue;
+            };
+
+            while let Some((request, next)) = spool.peek() {
+                let db = endpoints
+                    .pick()
+                    .database(&dbname, auth.read().unwrap().clone());
+                let result = match ttl.as_deref() {
+                    Some(ttl) => db.insert_with_hints(request, &[("ttl", ttl)]).await,
+                    None => db.insert(request).await,
+                }
+                .map_err(Failure::from);
+                match result {
+                    Ok(_) => spool.commit(next),
+                    Err(e) if recoverable(&e) => break,
+                    Err(e) => {
+                        log::warn!("discarding a spooled request rejected by the server: {e}");
+                        spool.discard(next);
+                    }
+                }
+            }
+        }
+    });
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{seq:020}.seg"))
+}
+
+fn open_segment(dir: &Path, seq: u64) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(segment_path(dir, seq))
+}
+
+fn checksum(payload: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(payload);
+    crc.sum()
+}
+
+/// The payload of the next record, `None` at the end of the segment or at a record that
+/// is torn or fails its checksum.
+fn read_record(file: &mut File) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; HEADER_LEN as usize];
+    match file.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+    // A corrupt length could be anything
+    let remaining = file
+        .metadata()?
+        .len()
+        .saturating_sub(file.stream_position()?);
+    if len as u64 > remaining {
+        return Ok(None);
+    }
+    let mut payload = vec![0u8; len];
+    match file.read_exact(&mut payload) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    Ok((checksum(&payload) == crc).then_some(payload))
+}
+
+fn read_record_at(path: &Path, offset: u64) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    read_record(&mut file)
+}
+
+/// The valid records from `offset`, and where they end.
+fn count_records(path: &Path, offset: u64) -> io::Result<(u64, u64)> {
+    let mut file = File::open(path)?;
+    let mut end = file.seek(SeekFrom::Start(offset))?;
+    let mut records = 0;
+    while let Some(payload) = read_record(&mut file)? {
+        records += 1;
+        end += HEADER_LEN + payload.len() as u64;
+    }
+    Ok((records, end))
+}
+
+/// `Seq Offset`, or `None` for a new spool.
+fn read_cursor(dir: &Path) -> io::Result<Option<Position>> {
+    let content = match fs::read_to_string(dir.join(CURSOR_FILE)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut parts = content.split_whitespace().map(str::parse::<u64>);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(seq)), Some(Ok(offset))) => Ok(Some(Position { seq, offset })),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid cursor file",
+        )),
+    }
+}
+
+fn write_cursor(dir: &Path, cursor: Position) -> io::Result<()> {
+    let tmp = dir.join(format!("{CURSOR_FILE}.tmp"));
+    fs::write(&tmp, format!("{} {}\n", cursor.seq, cursor.offset))?;
+    fs::rename(tmp, dir.join(CURSOR_FILE))
+}
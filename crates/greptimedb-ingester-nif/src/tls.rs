@@ -0,0 +1,271 @@
+use std::fs;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use greptimedb_ingester::channel_manager::TlsVerify;
+use rustler::{Atom, Encoder, Env, Term};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{CipherSuite, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+
+use crate::atoms;
+
+const PEM_MARKER: &[u8] = b"-----BEGIN ";
+
+/// Cipher suites the TLS stack can negotiate, by IANA name.
+pub const CIPHER_SUITES: &[&str] = &[
+    // TLS 1.3
+    "TLS_AES_256_GCM_SHA384",
+    "TLS_AES_128_GCM_SHA256",
+    "TLS_CHACHA20_POLY1305_SHA256",
+    // TLS 1.2
+    "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+    "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+    "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+    "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+    "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+    "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+];
+
+/// An invalid TLS option, encoded as `{tls_config, Field, Reason}`.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub field: Atom,
+    pub reason: String,
+}
+
+impl ConfigError {
+    pub fn new(field: Atom, reason: impl Into<String>) -> Self {
+        ConfigError {
+            field,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Encoder for ConfigError {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        (atoms::tls_config(), self.field, self.reason.as_str()).encode(env)
+    }
+}
+
+/// A certificate or key given either as a file path or as the PEM content itself.
+#[derive(Clone, Debug)]
+pub enum PemSource {
+    Path(String),
+    Pem(Vec<u8>),
+}
+
+impl PemSource {
+    /// Binaries holding a PEM block are taken as content, anything else as a path.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.windows(PEM_MARKER.len()).any(|w| w == PEM_MARKER) {
+            return Ok(PemSource::Pem(bytes.to_vec()));
+        }
+        String::from_utf8(bytes.to_vec())
+            .map(PemSource::Path)
+            .map_err(|_| "neither a path nor PEM".to_string())
+    }
+
+    fn load(&self) -> Result<Vec<u8>, String> {
+        match self {
+            PemSource::Path(path) => fs::read(path).map_err(|e| format!("{path}: {e}")),
+            PemSource::Pem(pem) => Ok(pem.clone()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TlsConfig {
+    pub verify: TlsVerify,
+    pub ca_cert: Option<PemSource>,
+    pub client_cert: Option<PemSource>,
+    pub client_key: Option<PemSource>,
+    pub cipher_suites: Vec<String>,
+}
+
+impl TlsConfig {
+    /// Check that certificates and key load and parse, come as a pair for mTLS, and that
+    /// the cipher suites are known, instead of failing on the first handshake.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(ca_cert) = &self.ca_cert {
+            check_certs(ca_cert).map_err(|e| ConfigError::new(atoms::ca_cert(), e))?;
+        }
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => {
+                check_certs(cert).map_err(|e| ConfigError::new(atoms::client_cert(), e))?;
+                check_key(key).map_err(|e| ConfigError::new(atoms::client_key(), e))?;
+            }
+            (Some(_), None) => {
+                return Err(ConfigError::new(
+                    atoms::client_key(),
+                    "required with client_cert",
+                ))
+            }
+            (None, Some(_)) => {
+                return Err(ConfigError::new(
+                    atoms::client_cert(),
+                    "required with client_key",
+                ))
+            }
+            (None, None) => {}
+        }
+        if let Some(unknown) = self
+            .cipher_suites
+            .iter()
+            .find(|name| !CIPHER_SUITES.contains(&name.as_str()))
+        {
+            return Err(ConfigError::new(
+                atoms::cipher_suites(),
+                format!("unknown cipher suite `{unknown}`"),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn check_certs(source: &PemSource) -> Result<(), String> {
+    if load_certs(source)?.is_empty() {
+        return Err("no certificate found".to_string());
+    }
+    Ok(())
+}
+
+fn check_key(source: &PemSource) -> Result<(), String> {
+    load_key(source).map(|_| ())
+}
+
+/// TLS settings of a connection, kept to rebuild its clients when certificates are reloaded.
+pub struct Tls {
+    pub config: TlsConfig,
+    /// FIPS restrictions apply, also to reloaded settings.
+    pub fips: bool,
+}
+
+impl Tls {
+    pub fn new(config: TlsConfig, fips: bool) -> Self {
+        Tls { config, fips }
+    }
+
+    /// The settings to open connections with. Certificates and key are read from their
+    /// files or taken as given, and stay in memory.
+    ///
+    /// The crypto provider is the process default, the FIPS one when installed.
+    pub fn client_config(&self) -> Result<Arc<ClientConfig>, String> {
+        let mut provider = CryptoProvider::get_default()
+            .map(|provider| provider.as_ref().clone())
+            .unwrap_or_else(rustls::crypto::aws_lc_rs::default_provider);
+        if !self.config.cipher_suites.is_empty() {
+            provider.cipher_suites.retain(|suite| {
+                let name = iana_name(suite.suite());
+                self.config.cipher_suites.iter().any(|n| *n == name)
+            });
+        }
+        let provider = Arc::new(provider);
+
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| e.to_string())?;
+        let builder = if matches!(self.config.verify, TlsVerify::VerifyNone) {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerifier(provider)))
+        } else {
+            builder.with_root_certificates(self.roots()?)
+        };
+        let mut config = match (&self.config.client_cert, &self.config.client_key) {
+            (Some(cert), Some(key)) => builder
+                .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+                .map_err(|e| e.to_string())?,
+            _ => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        Ok(Arc::new(config))
+    }
+
+    /// The configured CA, or the system roots without one.
+    fn roots(&self) -> Result<RootCertStore, String> {
+        let mut roots = RootCertStore::empty();
+        match &self.config.ca_cert {
+            Some(ca_cert) => {
+                roots.add_parsable_certificates(load_certs(ca_cert)?);
+            }
+            None => {
+                roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+            }
+        }
+        if roots.is_empty() {
+            return Err("no trusted CA certificate".to_string());
+        }
+        Ok(roots)
+    }
+}
+
+fn load_certs(source: &PemSource) -> Result<Vec<CertificateDer<'static>>, String> {
+    let pem = source.load()?;
+    rustls_pemfile::certs(&mut BufReader::new(pem.as_slice()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid PEM: {e}"))
+}
+
+fn load_key(source: &PemSource) -> Result<PrivateKeyDer<'static>, String> {
+    let pem = source.load()?;
+    rustls_pemfile::private_key(&mut BufReader::new(pem.as_slice()))
+        .map_err(|e| format!("invalid PEM: {e}"))?
+        .ok_or_else(|| "no private key found".to_string())
+}
+
+/// The IANA name of a suite, as listed in `CIPHER_SUITES`.
+pub fn iana_name(suite: CipherSuite) -> String {
+    format!("{suite:?}").replacen("TLS13_", "TLS_", 1)
+}
+
+/// `verify_none`: any server certificate is accepted, signatures are still checked.
+#[derive(Debug)]
+struct NoVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
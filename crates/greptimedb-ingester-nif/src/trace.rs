@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use arrow::record_batch::RecordBatch;
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_client::FlightServiceClient;
+use arrow_flight::Ticket;
+use futures::TryStreamExt;
+use greptime_proto::v1::greptime_database_client::GreptimeDatabaseClient;
+use greptime_proto::v1::greptime_request::Request;
+use greptime_proto::v1::greptime_response::Response;
+use greptime_proto::v1::query_request::Query;
+use greptime_proto::v1::{GreptimeRequest, QueryRequest, RequestHeader, RowInsertRequests};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{Status, TraceContextExt, Tracer, TracerProvider as _};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::Resource;
+use prost::Message;
+use tokio::runtime::Runtime;
+use tonic::codec::CompressionEncoding;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tonic::transport::Channel;
+
+use crate::retry::Failure;
+
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+const HINTS_HEADER: &str = "x-greptime-hints";
+
+/// The W3C trace context a call continues.
+#[derive(Clone, Debug)]
+pub struct TraceContext {
+    headers: HashMap<String, String>,
+}
+
+impl TraceContext {
+    pub fn new(traceparent: String, tracestate: Option<String>) -> Result<Self, String> {
+        if !valid_traceparent(&traceparent) {
+            return Err(format!("invalid traceparent `{traceparent}`"));
+        }
+        let mut headers = HashMap::from([(TRACEPARENT.to_string(), traceparent)]);
+        if let Some(tracestate) = tracestate {
+            headers.insert(TRACESTATE.to_string(), tracestate);
+        }
+        Ok(TraceContext { headers })
+    }
+}
+
+/// `version-traceid-parentid-flags`, lowercase hex, ids not all zeros.
+fn valid_traceparent(traceparent: &str) -> bool {
+    let parts: Vec<&str> = traceparent.split('-').collect();
+    let hex = |s: &str, len: usize| {
+        s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    };
+    let non_zero = |s: &str| s.bytes().any(|b| b != b'0');
+    parts.len() == 4
+        && hex(parts[0], 2)
+        && parts[0] != "ff"
+        && hex(parts[1], 32)
+        && non_zero(parts[1])
+        && hex(parts[2], 16)
+        && non_zero(parts[2])
+        && hex(parts[3], 2)
+}
+
+/// Exports the spans of a connection's calls over OTLP.
+pub struct Tracing {
+    provider: TracerProvider,
+    tracer: opentelemetry_sdk::trace::Tracer,
+    shut_down: AtomicBool,
+    /// The exporter runs there, kept until it is shut down.
+    _runtime: Arc<Runtime>,
+}
+
+impl Tracing {
+    /// `endpoint` is the gRPC address of the collector, e.g. `http://localhost:4317`.
+    /// Spans are batched and exported from `runtime`.
+    pub fn new(runtime: &Arc<Runtime>, endpoint: &str) -> Result<Self, String> {
+        let _guard = runtime.enter();
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+            .with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                "greptimedb_rs",
+            )]))
+            .build();
+        let tracer = provider.tracer("greptimedb_rs");
+        Ok(Tracing {
+            provider,
+            tracer,
+            shut_down: AtomicBool::new(false),
+            _runtime: runtime.clone(),
+        })
+    }
+
+    /// Export what is still batched. Blocks until the exporter is done, so not to be called
+    /// on a normal scheduler.
+    pub fn shutdown(&self) {
+        if !self.shut_down.swap(true, Ordering::Relaxed) {
+            let _ = self.provider.shutdown();
+        }
+    }
+}
+
+impl Drop for Tracing {
+    fn drop(&mut self) {
+        // Not disconnected, the connection is being collected. The runtime is dropped after,
+        // so the exporter is still there to take what is batched.
+        self.shutdown();
+    }
+}
+
+/// A span, or only the propagated context when spans are not exported. Calls without a
+/// trace context get an inactive one.
+pub struct Span<'a> {
+    tracer: Option<&'a opentelemetry_sdk::trace::Tracer>,
+    cx: Option<Context>,
+}
+
+impl<'a> Span<'a> {
+    fn inactive() -> Self {
+        Span {
+            tracer: None,
+            cx: None,
+        }
+    }
+
+    /// The span of a call continuing `parent`.
+    pub fn start(
+        tracing: Option<&'a Tracing>,
+        parent: Option<&TraceContext>,
+        name: &'static str,
+    ) -> Self {
+        let Some(parent) = parent else {
+            return Span::inactive();
+        };
+        let parent = TraceContextPropagator::new().extract(&parent.headers);
+        Span::child_of(tracing.map(|t| &t.tracer), &parent, name)
+    }
+
+    fn child_of(
+        tracer: Option<&'a opentelemetry_sdk::trace::Tracer>,
+        parent: &Context,
+        name: &'static str,
+    ) -> Self {
+        let cx = match tracer {
+            Some(tracer) => parent.with_span(tracer.start_with_context(name, parent)),
+            None => parent.clone(),
+        };
+        Span {
+            tracer,
+            cx: Some(cx),
+        }
+    }
+
+    /// A phase of the call.
+    pub fn child(&self, name: &'static str) -> Span<'a> {
+        match &self.cx {
+            Some(cx) => Span::child_of(self.tracer, cx, name),
+            None => Span::inactive(),
+        }
+    }
+
+    /// `traceparent` and `tracestate` for the requests made in this span, `None` when the
+    /// call is not traced.
+    pub fn headers(&self) -> Option<HashMap<String, String>> {
+        let cx = self.cx.as_ref()?;
+        let mut headers = HashMap::new();
+        TraceContextPropagator::new().inject_context(cx, &mut headers);
+        Some(headers)
+    }
+
+    pub fn end<T, E: ToString>(&self, result: &Result<T, E>) {
+        let (Some(_), Some(cx)) = (self.tracer, &self.cx) else {
+            return;
+        };
+        let span = cx.span();
+        if let Err(e) = result {
+            span.set_status(Status::error(e.to_string()));
+        }
+        span.end();
+    }
+}
+
+/// `Database::insert`, with the trace context in the request header and metadata, which
+/// the database doesn't let us set.
+pub async fn insert(
+    channel: Channel,
+    header: RequestHeader,
+    request: RowInsertRequests,
+    ttl: Option<&str>,
+    compression: Option<CompressionEncoding>,
+    trace_headers: HashMap<String, String>,
+) -> Result<u32, Failure> {
+    handle(
+        channel,
+        header,
+        Request::RowInserts(request),
+        ttl,
+        compression,
+        trace_headers,
+    )
+    .await
+}
+
+/// `Database::query`, with the trace context in the ticket's request header and metadata.
+pub async fn query(
+    channel: Channel,
+    mut header: RequestHeader,
+    sql: &str,
+    compression: Option<CompressionEncoding>,
+    trace_headers: HashMap<String, String>,
+) -> Result<Vec<RecordBatch>, Failure> {
+    let mut client = FlightServiceClient::new(channel);
+    if let Some(encoding) = compression {
+        client = client.send_compressed(encoding).accept_compressed(encoding);
+    }
+
+    header.tracing_context = trace_headers.clone();
+    let ticket = GreptimeRequest {
+        header: Some(header),
+        request: Some(Request::Query(QueryRequest {
+            query: Some(Query::Sql(sql.to_string())),
+        })),
+    };
+    let mut request = tonic::Request::new(Ticket {
+        ticket: ticket.encode_to_vec().into(),
+    });
+    set_metadata(request.metadata_mut(), &trace_headers);
+
+    let flight_data = client.do_get(request).await?.into_inner();
+    FlightRecordBatchStream::new_from_flight_data(flight_data.map_err(FlightError::from))
+        .try_collect()
+        .await
+        .map_err(|e| match e {
+            FlightError::Tonic(status) => Failure::from(status),
+            e => Failure::from(e.to_string()),
+        })
+}
+
+fn set_metadata(metadata: &mut MetadataMap, trace_headers: &HashMap<String, String>) {
+    for (key, value) in trace_headers {
+        if let (Ok(key), Ok(value)) = (
+            MetadataKey::from_bytes(key.as_bytes()),
+            MetadataValue::try_from(value.as_str()),
+        ) {
+            metadata.insert(key, value);
+        }
+    }
+}
+
+/// Send a request returning affected rows, with the trace context if any.
+pub async fn handle(
+    channel: Channel,
+    mut header: RequestHeader,
+    request: Request,
+    ttl: Option<&str>,
+    compression: Option<CompressionEncoding>,
+    trace_headers: HashMap<String, String>,
+) -> Result<u32, Failure> {
+    let mut client = GreptimeDatabaseClient::new(channel);
+    if let Some(encoding) = compression {
+        client = client.send_compressed(encoding).accept_compressed(encoding);
+    }
+
+    let mut request = tonic::Request::new(GreptimeRequest {
+        header: None,
+        request: Some(request),
+    });
+    let metadata = request.metadata_mut();
+    set_metadata(metadata, &trace_headers);
+    if let Some(ttl) = ttl {
+        let value = MetadataValue::try_from(format!("ttl={ttl}")).map_err(|e| e.to_string())?;
+        metadata.insert(HINTS_HEADER, value);
+    }
+    header.tracing_context = trace_headers;
+    request.get_mut().header = Some(header);
+
+    let response = client.handle(request).await?.into_inner();
+    match response.response {
+        Some(Response::AffectedRows(affected)) => Ok(affected.value),
+        _ => Err("unexpected response to a write".to_string().into()),
+    }
+}
@@ -10,7 +10,24 @@ use arrow::record_batch::RecordBatch;
 use greptimedb_ingester::api::v1::value::ValueData;
 use greptimedb_ingester::api::v1::{ColumnDataType, Value as ProtoValue};
 use greptimedb_ingester::Value;
-use rustler::{Encoder, Env, Term};
+use rustler::{Encoder, Env, Term, TermType};
+
+/// How strictly a term must match the column type it is written to.
+///
+/// Each level accepts everything the previous one does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Coercion {
+    /// Only terms of the exact Erlang type are accepted.
+    #[default]
+    Strict,
+    /// Integers and floats convert into each other (floats are truncated, with range checks),
+    /// and binaries holding a number are parsed into numeric columns.
+    Widen,
+    /// Atoms and numbers are also accepted by string columns.
+    Stringify,
+    /// Values that still cannot be converted are written as null.
+    NullOnError,
+}
 
 macro_rules! convert_int {
     ($val:expr, $variant:ident, $intermediate:ty, $target:ty) => {
@@ -45,7 +62,23 @@ macro_rules! convert_direct {
     };
 }
 
-pub fn term_to_value(val: &Term, dtype: ColumnDataType) -> rustler::NifResult<Value> {
+pub fn term_to_value(
+    val: &Term,
+    dtype: ColumnDataType,
+    coercion: Coercion,
+) -> rustler::NifResult<Value> {
+    match strict_term_to_value(val, dtype) {
+        Ok(value) => Ok(value),
+        Err(e) if coercion == Coercion::Strict => Err(e),
+        Err(e) => match coerce_term_to_value(val, dtype, coercion) {
+            Some(value) => Ok(value),
+            None if coercion == Coercion::NullOnError => Ok(Value::Null),
+            None => Err(e),
+        },
+    }
+}
+
+fn strict_term_to_value(val: &Term, dtype: ColumnDataType) -> rustler::NifResult<Value> {
     match dtype {
         ColumnDataType::Boolean => convert_direct!(val, Boolean, bool),
         ColumnDataType::Int8 => convert_int!(val, Int8, i64, i8),
@@ -66,6 +99,7 @@ pub fn term_to_value(val: &Term, dtype: ColumnDataType) -> rustler::NifResult<Va
                 convert_direct!(val, Binary, Vec<u8>)
             }
         }
+        ColumnDataType::Json => convert_direct!(val, Json, String),
         ColumnDataType::Date => convert_int!(val, Date, i32, i32),
         ColumnDataType::Datetime => convert_int!(val, Datetime, i64, i64),
         ColumnDataType::TimestampSecond => convert_int!(val, TimestampSecond, i64, i64),
@@ -76,6 +110,106 @@ pub fn term_to_value(val: &Term, dtype: ColumnDataType) -> rustler::NifResult<Va
     }
 }
 
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn to_i128(&self) -> Option<i128> {
+        match *self {
+            Number::Int(i) => Some(i),
+            // NaN and infinities, as parsed from `"NaN"` or `"inf"`, are no integer
+            Number::Float(f) if f.is_finite() => Some(f.trunc() as i128),
+            Number::Float(_) => None,
+        }
+    }
+
+    /// `None` when a finite value is out of the range of `f32`, instead of becoming infinite.
+    fn to_f32(&self) -> Option<f32> {
+        let f = self.to_f64();
+        (!f.is_finite() || f.abs() <= f32::MAX as f64).then_some(f as f32)
+    }
+
+    fn to_f64(&self) -> f64 {
+        match *self {
+            Number::Int(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+}
+
+fn term_to_number(val: &Term) -> Option<Number> {
+    match val.get_type() {
+        TermType::Integer => val
+            .decode::<i64>()
+            .map(i128::from)
+            .or_else(|_| val.decode::<u64>().map(i128::from))
+            .ok()
+            .map(Number::Int),
+        TermType::Float => val.decode::<f64>().ok().map(Number::Float),
+        TermType::Binary => {
+            let bin = val.decode::<rustler::Binary>().ok()?;
+            let s = std::str::from_utf8(bin.as_slice()).ok()?.trim();
+            s.parse::<i128>()
+                .map(Number::Int)
+                .or_else(|_| s.parse::<f64>().map(Number::Float))
+                .ok()
+        }
+        _ => None,
+    }
+}
+
+fn term_to_display_string(val: &Term) -> Option<String> {
+    match val.get_type() {
+        TermType::Atom => val.atom_to_string().ok(),
+        TermType::Integer => val
+            .decode::<i64>()
+            .map(|i| i.to_string())
+            .or_else(|_| val.decode::<u64>().map(|u| u.to_string()))
+            .ok(),
+        TermType::Float => val.decode::<f64>().ok().map(|f| f.to_string()),
+        _ => None,
+    }
+}
+
+macro_rules! coerce_int {
+    ($val:expr, $variant:ident, $target:ty) => {
+        term_to_number($val)
+            .and_then(|n| n.to_i128())
+            .and_then(|i| <$target>::try_from(i).ok())
+            .map(Value::$variant)
+    };
+}
+
+/// Lenient conversion used when the strict one fails and `coercion` allows it.
+fn coerce_term_to_value(val: &Term, dtype: ColumnDataType, coercion: Coercion) -> Option<Value> {
+    match dtype {
+        ColumnDataType::Int8 => coerce_int!(val, Int8, i8),
+        ColumnDataType::Int16 => coerce_int!(val, Int16, i16),
+        ColumnDataType::Int32 => coerce_int!(val, Int32, i32),
+        ColumnDataType::Int64 => coerce_int!(val, Int64, i64),
+        ColumnDataType::Uint8 => coerce_int!(val, Uint8, u8),
+        ColumnDataType::Uint16 => coerce_int!(val, Uint16, u16),
+        ColumnDataType::Uint32 => coerce_int!(val, Uint32, u32),
+        ColumnDataType::Uint64 => coerce_int!(val, Uint64, u64),
+        ColumnDataType::Float32 => term_to_number(val)
+            .and_then(|n| n.to_f32())
+            .map(Value::Float32),
+        ColumnDataType::Float64 => term_to_number(val).map(|n| Value::Float64(n.to_f64())),
+        ColumnDataType::String if coercion >= Coercion::Stringify => {
+            term_to_display_string(val).map(Value::String)
+        }
+        ColumnDataType::Date => coerce_int!(val, Date, i32),
+        ColumnDataType::Datetime => coerce_int!(val, Datetime, i64),
+        ColumnDataType::TimestampSecond => coerce_int!(val, TimestampSecond, i64),
+        ColumnDataType::TimestampMillisecond => coerce_int!(val, TimestampMillisecond, i64),
+        ColumnDataType::TimestampMicrosecond => coerce_int!(val, TimestampMicrosecond, i64),
+        ColumnDataType::TimestampNanosecond => coerce_int!(val, TimestampNanosecond, i64),
+        _ => None,
+    }
+}
+
 pub fn value_to_proto_value(value: Value) -> ProtoValue {
     let value_data = match value {
         Value::Boolean(v) => Some(ValueData::BoolValue(v)),
@@ -108,8 +242,12 @@ pub fn value_to_proto_value(value: Value) -> ProtoValue {
     ProtoValue { value_data }
 }
 
-pub fn term_to_proto_value(val: &Term, dtype: ColumnDataType) -> rustler::NifResult<ProtoValue> {
-    let value = term_to_value(val, dtype)?;
+pub fn term_to_proto_value(
+    val: &Term,
+    dtype: ColumnDataType,
+    coercion: Coercion,
+) -> rustler::NifResult<ProtoValue> {
+    let value = term_to_value(val, dtype, coercion)?;
     Ok(value_to_proto_value(value))
 }
 
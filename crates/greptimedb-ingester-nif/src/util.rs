@@ -1,14 +1,173 @@
 use crate::atoms;
 use crate::types;
-use greptimedb_ingester::api::v1::{ColumnDataType, ColumnSchema, Row as ProtoRow, SemanticType};
+use greptimedb_ingester::api::v1::column_data_type_extension::TypeExt;
+use greptimedb_ingester::api::v1::{
+    ColumnDataType, ColumnDataTypeExtension, ColumnSchema, JsonTypeExtension, Row as ProtoRow,
+    SemanticType, Value as ProtoValue,
+};
 use greptimedb_ingester::helpers::schema::{field, tag, timestamp};
 use greptimedb_ingester::helpers::values::none_value;
 use greptimedb_ingester::{Row, Rows, TableSchema, Value};
-use rustler::{Encoder, Term, TermType};
+use rustler::{Encoder, Env, Term, TermType};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How to fill the time index of a row that carries neither `timestamp` nor `ts`.
+#[derive(Clone, Debug)]
+pub enum DefaultTimestamp {
+    /// Use the current wall clock, in the precision of the time index column.
+    Now,
+    /// Reject the batch.
+    Error,
+    /// Take the value of the named field (or tag) of the same row.
+    Column(String),
+}
+
+/// Type wanted for a column of an auto-created table, overriding inference.
+#[derive(Clone, Debug)]
+pub struct ColumnHint {
+    pub dtype: ColumnDataType,
+    /// `None` keeps the column a tag or field according to where it appears in the rows.
+    pub semantic: Option<SemanticType>,
+}
+
+/// Column name to hint.
+pub type SchemaHints = HashMap<String, ColumnHint>;
+
+/// Options controlling how row terms are converted, shared by `insert` and `stream_write`.
+#[derive(Clone, Debug, Default)]
+pub struct ConvertOptions {
+    /// `None` keeps the timestamp null when a row has none.
+    pub default_timestamp: Option<DefaultTimestamp>,
+    pub coercion: types::Coercion,
+    pub schema_hints: SchemaHints,
+}
+
+/// A row rejected during conversion, reported back instead of failing the whole batch.
+pub struct RowError<'a> {
+    /// 1-based position of the row in the batch.
+    pub row: usize,
+    pub column: String,
+    pub reason: Term<'a>,
+}
+
+impl Encoder for RowError<'_> {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        (self.row, self.column.as_str(), self.reason).encode(env)
+    }
+}
+
+fn error_reason<'a>(env: Env<'a>, err: rustler::Error) -> Term<'a> {
+    match err {
+        rustler::Error::RaiseTerm(reason) | rustler::Error::Term(reason) => reason.encode(env),
+        _ => rustler::types::atom::badarg().encode(env),
+    }
+}
+
+/// Record a row conversion failure in `errors`, or propagate it when isolation is off.
+fn reject_row<'a>(
+    env: Env<'a>,
+    errors: &mut Option<&mut Vec<RowError<'a>>>,
+    row: usize,
+    column: &str,
+    err: rustler::Error,
+) -> rustler::NifResult<()> {
+    match errors {
+        Some(errors) => {
+            errors.push(RowError {
+                row,
+                column: column.to_string(),
+                reason: error_reason(env, err),
+            });
+            Ok(())
+        }
+        None => Err(err),
+    }
+}
+
+/// Convert the values of one row, returning the index of the offending column on failure.
+/// `coercion` gives the coercion of the column at an index.
+fn row_to_proto_values<'a>(
+    col_meta: &[(SemanticType, Term<'a>, ColumnDataType)],
+    fields_term: Option<Term<'a>>,
+    tags_term: Option<Term<'a>>,
+    ts_term: Option<Term<'a>>,
+    coercion: impl Fn(usize) -> types::Coercion,
+) -> Result<Vec<ProtoValue>, (usize, rustler::Error)> {
+    let mut values = Vec::with_capacity(col_meta.len());
+
+    for (index, (semantic, key_term, dtype)) in col_meta.iter().enumerate() {
+        let val_term = match semantic {
+            SemanticType::Field => find_value_in_map(fields_term, *key_term),
+            SemanticType::Tag => find_value_in_map(tags_term, *key_term),
+            SemanticType::Timestamp => ts_term,
+        };
+
+        let val = if let Some(t) = val_term {
+            types::term_to_proto_value(&t, *dtype, coercion(index)).map_err(|e| (index, e))?
+        } else {
+            none_value()
+        };
+        values.push(val);
+    }
+    Ok(values)
+}
+
+/// Resolve the timestamp term of a row, falling back to `opts.default_timestamp`.
+fn row_timestamp<'a>(
+    env: Env<'a>,
+    row_term: Term<'a>,
+    fields_term: Option<Term<'a>>,
+    tags_term: Option<Term<'a>>,
+    dtype: ColumnDataType,
+    opts: &ConvertOptions,
+) -> rustler::NifResult<Option<Term<'a>>> {
+    // Timestamp can be under "timestamp" or "ts" (atom keys only)
+    let ts_term = row_term
+        .map_get(atoms::timestamp().to_term(env))
+        .ok()
+        .or_else(|| row_term.map_get(atoms::ts().to_term(env)).ok());
+    if ts_term.is_some() {
+        return Ok(ts_term);
+    }
+
+    match &opts.default_timestamp {
+        None => Ok(None),
+        Some(DefaultTimestamp::Now) => Ok(Some(now_in_precision(dtype).encode(env))),
+        Some(DefaultTimestamp::Error) => Err(rustler::Error::RaiseTerm(Box::new(
+            "Row has no timestamp".to_string(),
+        ))),
+        Some(DefaultTimestamp::Column(name)) => {
+            let key_term = name.encode(env);
+            match find_value_in_map(fields_term, key_term)
+                .or_else(|| find_value_in_map(tags_term, key_term))
+            {
+                Some(t) => Ok(Some(t)),
+                None => Err(rustler::Error::RaiseTerm(Box::new(format!(
+                    "Row has no timestamp and no `{name}` column"
+                )))),
+            }
+        }
+    }
+}
+
+/// Current wall clock in the unit of the given time index type.
+fn now_in_precision(dtype: ColumnDataType) -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    match dtype {
+        ColumnDataType::TimestampSecond => now.as_secs() as i64,
+        ColumnDataType::TimestampMicrosecond => now.as_micros() as i64,
+        ColumnDataType::TimestampNanosecond => now.as_nanos() as i64,
+        _ => now.as_millis() as i64,
+    }
+}
 
 pub fn terms_to_rows<'a>(
     table_schema: &TableSchema,
     rows_term: Vec<Term<'a>>,
+    opts: &ConvertOptions,
 ) -> rustler::NifResult<Rows> {
     let column_schemas = table_schema.columns();
     let mut greptime_rows = Rows::new(column_schemas, rows_term.len(), 1024)
@@ -29,19 +188,13 @@ pub fn terms_to_rows<'a>(
     // Pre-compute static atom keys only
     let atom_fields = atoms::fields().to_term(env);
     let atom_tags = atoms::tags().to_term(env);
-    let atom_timestamp = atoms::timestamp().to_term(env);
-    let atom_ts = atoms::ts().to_term(env);
+    let ts_dtype = time_index_dtype(table_schema);
 
     for row_term in rows_term {
         // Retrieve sub-maps directly from the row term (atom keys only)
         let fields_term = row_term.map_get(atom_fields).ok();
         let tags_term = row_term.map_get(atom_tags).ok();
-
-        // Timestamp can be under "timestamp" or "ts" (atom keys only)
-        let ts_term = row_term
-            .map_get(atom_timestamp)
-            .ok()
-            .or_else(|| row_term.map_get(atom_ts).ok());
+        let ts_term = row_timestamp(env, row_term, fields_term, tags_term, ts_dtype, opts)?;
 
         let mut values = Vec::with_capacity(col_meta.len());
 
@@ -53,7 +206,7 @@ pub fn terms_to_rows<'a>(
             };
 
             let val = if let Some(t) = val_term {
-                types::term_to_value(&t, *dtype)?
+                types::term_to_value(&t, *dtype, opts.coercion)?
             } else {
                 Value::Null
             };
@@ -67,9 +220,15 @@ pub fn terms_to_rows<'a>(
     Ok(greptime_rows)
 }
 
+/// Convert rows against an existing table schema.
+///
+/// When `errors` is given, rows that fail to convert are recorded there and left out of
+/// the result instead of failing the whole batch.
 pub fn terms_to_proto_rows_using_schema<'a>(
     table_schema: &TableSchema,
     rows_term: Vec<Term<'a>>,
+    opts: &ConvertOptions,
+    mut errors: Option<&mut Vec<RowError<'a>>>,
 ) -> rustler::NifResult<Vec<ProtoRow>> {
     if rows_term.is_empty() {
         return Ok(Vec::new());
@@ -87,50 +246,49 @@ pub fn terms_to_proto_rows_using_schema<'a>(
     // Pre-compute static atom keys only
     let atom_fields = atoms::fields().to_term(env);
     let atom_tags = atoms::tags().to_term(env);
-    let atom_timestamp = atoms::timestamp().to_term(env);
-    let atom_ts = atoms::ts().to_term(env);
+    let ts_dtype = time_index_dtype(table_schema);
+    let ts_name = column_schemas
+        .iter()
+        .find(|c| c.semantic_type == SemanticType::Timestamp)
+        .map_or("ts", |c| c.name.as_str());
 
     let mut rows = Vec::with_capacity(rows_term.len());
 
-    for row_term in rows_term {
+    for (index, row_term) in rows_term.into_iter().enumerate() {
         let fields_term = row_term.map_get(atom_fields).ok();
         let tags_term = row_term.map_get(atom_tags).ok();
-        let ts_term = row_term
-            .map_get(atom_timestamp)
-            .ok()
-            .or_else(|| row_term.map_get(atom_ts).ok());
-
-        let mut values = Vec::with_capacity(col_meta.len());
-
-        for (semantic, key_term, dtype) in &col_meta {
-            let val_term = match semantic {
-                SemanticType::Field => fields_term.and_then(|map| map.map_get(*key_term).ok()),
-                SemanticType::Tag => tags_term.and_then(|map| map.map_get(*key_term).ok()),
-                SemanticType::Timestamp => ts_term,
-            };
+        let ts_term = match row_timestamp(env, row_term, fields_term, tags_term, ts_dtype, opts) {
+            Ok(ts_term) => ts_term,
+            Err(e) => {
+                reject_row(env, &mut errors, index + 1, ts_name, e)?;
+                continue;
+            }
+        };
 
-            let val = if let Some(t) = val_term {
-                types::term_to_proto_value(&t, *dtype)?
-            } else {
-                none_value()
-            };
-            values.push(val);
+        match row_to_proto_values(&col_meta, fields_term, tags_term, ts_term, |_| {
+            opts.coercion
+        }) {
+            Ok(values) => rows.push(ProtoRow { values }),
+            Err((col, e)) => reject_row(env, &mut errors, index + 1, &column_schemas[col].name, e)?,
         }
-        rows.push(ProtoRow { values });
     }
     Ok(rows)
 }
 
+/// Infer a schema from the rows and convert them, for tables that don't exist yet.
+///
+/// `errors` behaves as in [`terms_to_proto_rows_using_schema`].
 pub fn terms_to_schema_and_rows<'a>(
     rows_term: Vec<Term<'a>>,
     ts_column: &str,
+    opts: &ConvertOptions,
+    mut errors: Option<&mut Vec<RowError<'a>>>,
 ) -> rustler::NifResult<(Vec<ColumnSchema>, Vec<ProtoRow>)> {
     if rows_term.is_empty() {
         return Ok((vec![], vec![]));
     }
 
     let env = rows_term[0].get_env();
-    let first_row = rows_term[0];
 
     // Pre-compute static atom keys only
     let atom_fields = atoms::fields().to_term(env);
@@ -138,112 +296,223 @@ pub fn terms_to_schema_and_rows<'a>(
     let atom_timestamp = atoms::timestamp().to_term(env);
     let atom_ts = atoms::ts().to_term(env);
 
-    let fields_term = first_row.map_get(atom_fields).ok();
-    let tags_term = first_row.map_get(atom_tags).ok();
-
     // --- Infer Schema ---
-    let mut schema = Vec::new();
-
-    // 1. Tags
-    if let Some(map) = tags_term {
-        let mut keys: Vec<Term> = match map.decode::<rustler::MapIterator>() {
-            Ok(iter) => iter.map(|(k, _)| k).collect(),
-            Err(_) => return Err(rustler::Error::BadArg),
-        };
-        keys.sort();
-
-        for key in keys {
-            let val = map.map_get(key)?;
-            let dtype = infer_dtype(val);
-            let name = term_to_string(key)?;
-            schema.push(tag(&name, dtype));
+    // Columns are the union over all rows, so a tag or field that first shows up
+    // in a later row is not dropped.
+    let mut tag_types = BTreeMap::new();
+    let mut field_types = BTreeMap::new();
+    // Columns whose values are of different types, see `merge_dtype`
+    let mut widened = BTreeSet::new();
+    let mut conflicts = Vec::new();
+    let mut has_ts = false;
+
+    for row_term in &rows_term {
+        if let Ok(map) = row_term.map_get(atom_tags) {
+            infer_columns(map, &mut tag_types, &mut widened)?;
         }
+        if let Ok(map) = row_term.map_get(atom_fields) {
+            infer_columns(map, &mut field_types, &mut widened)?;
+        }
+        has_ts =
+            has_ts || row_term.map_get(atom_timestamp).is_ok() || row_term.map_get(atom_ts).is_ok();
     }
 
-    // 2. Fields
-    if let Some(map) = fields_term {
-        let mut keys: Vec<Term> = match map.decode::<rustler::MapIterator>() {
-            Ok(iter) => iter.map(|(k, _)| k).collect(),
-            Err(_) => return Err(rustler::Error::BadArg),
-        };
-        keys.sort();
-
-        for key in keys {
-            let val = map.map_get(key)?;
-            let dtype = infer_dtype(val);
-            let name = term_to_string(key)?;
-            schema.push(field(&name, dtype));
+    // A hinted column takes the hinted type, whatever its values look like
+    widened.retain(|name| !opts.schema_hints.contains_key(name));
+    for name in tag_types.keys() {
+        if field_types.contains_key(name) {
+            conflicts.push((name.clone(), "used as both tag and field".to_string()));
         }
     }
+    if !conflicts.is_empty() {
+        return Err(rustler::Error::RaiseTerm(Box::new((
+            atoms::schema_conflict(),
+            conflicts,
+        ))));
+    }
 
-    // 3. Timestamp
-    let ts_term = first_row
-        .map_get(atom_timestamp)
-        .ok()
-        .or_else(|| first_row.map_get(atom_ts).ok());
-
-    if ts_term.is_some() {
-        let ts_name = if ts_column.is_empty() {
-            "ts"
-        } else {
-            ts_column
-        };
-        schema.push(timestamp(ts_name, ColumnDataType::TimestampMillisecond));
+    let mut schema = Vec::with_capacity(tag_types.len() + field_types.len() + 1);
+    // Where each column is looked up in a row; a hint may change the semantic
+    // type of the column but not where its values come from.
+    let mut sources = Vec::with_capacity(schema.capacity());
+
+    // 1. Tags, 2. Fields
+    for (source, columns) in [
+        (SemanticType::Tag, &tag_types),
+        (SemanticType::Field, &field_types),
+    ] {
+        for (name, dtype) in columns {
+            let hint = opts.schema_hints.get(name);
+            let dtype = hint
+                .map(|h| h.dtype)
+                .or(*dtype)
+                .unwrap_or(ColumnDataType::String);
+            let semantic = hint.and_then(|h| h.semantic).unwrap_or(source);
+            schema.push(column_schema(name, dtype, semantic));
+            sources.push(source);
+        }
     }
 
-    // --- Build Rows ---
-    struct ColMeta<'a> {
-        semantic: SemanticType,
-        key_term: Term<'a>,
-        dtype: ColumnDataType,
+    // 3. Timestamp
+    let ts_name = if ts_column.is_empty() {
+        "ts"
+    } else {
+        ts_column
+    };
+    let ts_dtype = opts
+        .schema_hints
+        .get(ts_name)
+        .map_or(ColumnDataType::TimestampMillisecond, |h| h.dtype);
+    if has_ts || opts.default_timestamp.is_some() {
+        if !is_timestamp(ts_dtype) {
+            return Err(rustler::Error::RaiseTerm(Box::new(format!(
+                "schema hint for time index `{ts_name}` is not a timestamp type"
+            ))));
+        }
+        schema.push(column_schema(ts_name, ts_dtype, SemanticType::Timestamp));
+        sources.push(SemanticType::Timestamp);
     }
 
-    let mut col_meta_list = Vec::with_capacity(schema.len());
-    for col in &schema {
-        let key_term = col.column_name.encode(env);
-        let semantic = SemanticType::try_from(col.semantic_type).unwrap_or(SemanticType::Field);
-        let dtype = ColumnDataType::try_from(col.datatype).unwrap_or(ColumnDataType::String);
+    // Values of a widened column are converted into its type whatever the coercion,
+    // the other columns follow the configured one.
+    let coercions: Vec<types::Coercion> = schema
+        .iter()
+        .map(|column| {
+            if widened.contains(&column.column_name) {
+                opts.coercion.max(types::Coercion::Stringify)
+            } else {
+                opts.coercion
+            }
+        })
+        .collect();
 
-        col_meta_list.push(ColMeta {
-            semantic,
-            key_term,
-            dtype,
-        });
-    }
+    // --- Build Rows ---
+    let col_meta: Vec<(SemanticType, Term<'a>, ColumnDataType)> = schema
+        .iter()
+        .zip(&sources)
+        .map(|(col, source)| {
+            (
+                *source,
+                col.column_name.encode(env),
+                ColumnDataType::try_from(col.datatype).unwrap_or(ColumnDataType::String),
+            )
+        })
+        .collect();
 
     let mut rows = Vec::with_capacity(rows_term.len());
 
-    use greptimedb_ingester::helpers::values::none_value;
-
-    for row_term in rows_term {
+    for (index, row_term) in rows_term.into_iter().enumerate() {
         let fields_map = row_term.map_get(atom_fields).ok();
         let tags_map = row_term.map_get(atom_tags).ok();
 
-        let row_ts_term = row_term
-            .map_get(atom_timestamp)
-            .ok()
-            .or_else(|| row_term.map_get(atom_ts).ok());
+        let row_ts_term = match row_timestamp(env, row_term, fields_map, tags_map, ts_dtype, opts) {
+            Ok(ts_term) => ts_term,
+            Err(e) => {
+                reject_row(env, &mut errors, index + 1, ts_name, e)?;
+                continue;
+            }
+        };
+
+        match row_to_proto_values(&col_meta, fields_map, tags_map, row_ts_term, |i| {
+            coercions[i]
+        }) {
+            Ok(values) => rows.push(ProtoRow { values }),
+            Err((col, e)) => reject_row(env, &mut errors, index + 1, &schema[col].column_name, e)?,
+        }
+    }
 
-        let mut values = Vec::with_capacity(schema.len());
+    Ok((schema, rows))
+}
 
-        for meta in &col_meta_list {
-            let val_term = match meta.semantic {
-                SemanticType::Tag => find_value_in_map(tags_map, meta.key_term),
-                SemanticType::Field => find_value_in_map(fields_map, meta.key_term),
-                SemanticType::Timestamp => row_ts_term,
-            };
+/// Rough size of row terms, counting the bytes of binaries and 8 for any other scalar.
+pub fn approx_size(terms: &[Term]) -> u64 {
+    fn size(term: Term) -> u64 {
+        match term.get_type() {
+            TermType::Binary => term
+                .decode::<rustler::Binary>()
+                .map_or(8, |bin| bin.len() as u64),
+            TermType::Map => term
+                .decode::<rustler::MapIterator>()
+                .map_or(8, |map| map.map(|(k, v)| size(k) + size(v)).sum()),
+            TermType::List => term
+                .decode::<rustler::ListIterator>()
+                .map_or(8, |list| list.map(size).sum()),
+            TermType::Tuple => rustler::types::tuple::get_tuple(term)
+                .map_or(8, |elems| elems.into_iter().map(size).sum()),
+            _ => 8,
+        }
+    }
+    terms.iter().map(|term| size(*term)).sum()
+}
 
-            let val = if let Some(t) = val_term {
-                types::term_to_proto_value(&t, meta.dtype)?
-            } else {
-                none_value()
-            };
-            values.push(val);
+fn column_schema(name: &str, dtype: ColumnDataType, semantic: SemanticType) -> ColumnSchema {
+    let mut column = match semantic {
+        SemanticType::Tag => tag(name, dtype),
+        SemanticType::Field => field(name, dtype),
+        SemanticType::Timestamp => timestamp(name, dtype),
+    };
+    if dtype == ColumnDataType::Json {
+        column.datatype_extension = Some(ColumnDataTypeExtension {
+            type_ext: Some(TypeExt::JsonType(JsonTypeExtension::JsonBinary as i32)),
+        });
+    }
+    column
+}
+
+/// Merge the value types of one tags/fields map into `columns`, adding the columns
+/// whose type had to be widened to `widened`.
+///
+/// A column maps to `None` while only nulls have been seen for it.
+fn infer_columns(
+    map: Term,
+    columns: &mut BTreeMap<String, Option<ColumnDataType>>,
+    widened: &mut BTreeSet<String>,
+) -> rustler::NifResult<()> {
+    let iter = match map.decode::<rustler::MapIterator>() {
+        Ok(iter) => iter,
+        Err(_) => return Err(rustler::Error::BadArg),
+    };
+
+    for (key, val) in iter {
+        let name = term_to_string(key)?;
+        let dtype = if val == rustler::types::atom::nil().to_term(val.get_env()) {
+            None
+        } else {
+            Some(infer_dtype(val))
+        };
+
+        let entry = columns.entry(name.clone()).or_insert(None);
+        match (*entry, dtype) {
+            (_, None) => {}
+            (None, Some(dtype)) => *entry = Some(dtype),
+            (Some(prev), Some(dtype)) if prev == dtype => {}
+            (Some(prev), Some(dtype)) => {
+                let merged = merge_dtype(prev, dtype);
+                let first_mix = widened.insert(name.clone());
+                if merged == ColumnDataType::String && (first_mix || prev != merged) {
+                    log::warn!("column `{name}` holds {prev:?} and {dtype:?}, inferred as String");
+                }
+                *entry = Some(merged);
+            }
         }
-        rows.push(ProtoRow { values });
     }
+    Ok(())
+}
 
-    Ok((schema, rows))
+/// Widen two different inferred types into one that holds both: integers and floats widen
+/// to Float64, any other mix to String. `Int64` and `Uint64` are such a mix, no numeric
+/// type holds both exactly.
+fn merge_dtype(a: ColumnDataType, b: ColumnDataType) -> ColumnDataType {
+    let is_integer =
+        |dtype: ColumnDataType| matches!(dtype, ColumnDataType::Int64 | ColumnDataType::Uint64);
+
+    if (is_integer(a) && b == ColumnDataType::Float64)
+        || (a == ColumnDataType::Float64 && is_integer(b))
+    {
+        ColumnDataType::Float64
+    } else {
+        ColumnDataType::String
+    }
 }
 
 fn infer_dtype(term: Term) -> ColumnDataType {
@@ -272,6 +541,25 @@ fn infer_dtype(term: Term) -> ColumnDataType {
     }
 }
 
+fn is_timestamp(dtype: ColumnDataType) -> bool {
+    matches!(
+        dtype,
+        ColumnDataType::TimestampSecond
+            | ColumnDataType::TimestampMillisecond
+            | ColumnDataType::TimestampMicrosecond
+            | ColumnDataType::TimestampNanosecond
+    )
+}
+
+fn time_index_dtype(table_schema: &TableSchema) -> ColumnDataType {
+    table_schema
+        .columns()
+        .iter()
+        .find(|c| c.semantic_type == SemanticType::Timestamp)
+        .map(|c| c.data_type)
+        .unwrap_or(ColumnDataType::TimestampMillisecond)
+}
+
 fn term_to_string(term: Term) -> rustler::NifResult<String> {
     if let Ok(s) = term.decode::<String>() {
         Ok(s)
@@ -14,7 +14,8 @@ fi
 # touch the build.rs to force cargo to rerun build script and generate libpath file
 touch "${BUILD_SCRIPT}"
 
-cargo build --release
+# e.g. CARGO_FEATURES=fips for a FIPS-capable crypto provider
+cargo build --release ${CARGO_FEATURES:+--features "${CARGO_FEATURES}"}
 
 # Should always be `.so`, OTP on macos won't load `.dylib` files.
 cp $(cat ./libpath) "${NIF_PATH}"
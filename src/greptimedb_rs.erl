@@ -21,32 +21,96 @@
 %% Connection and Disconnection
 -export([
     start_client/1,
-    stop_client/1
+    stop_client/1,
+    connection_status/1,
+    update_auth/2,
+    reload_tls/2,
+    ping/1,
+    ping/2,
+    stats/1,
+    spool_status/1
 ]).
 
 %% Write - Batch Write, onshot streaming write
 -export([
     insert/3,
-    insert_async/4
+    insert/4,
+    insert_async/4,
+    insert_async/5,
+    delete/3
 ]).
 
 %% Write - Execute Query
 -export([
     query/2,
-    query_async/3
+    query/3,
+    query_async/3,
+    query_async/4
+]).
+
+%% Schema introspection
+-export([
+    list_databases/1,
+    list_tables/2,
+    describe_table/2,
+    table_exists/2
 ]).
 
 %% Write - Streaming Write with persistent stream client
 -export([
     stream_start/3,
+    stream_start/4,
     stream_close/1,
     stream_write/2,
-    stream_write_async/3
+    stream_write/3,
+    stream_write_async/3,
+    stream_write_async/4
+]).
+
+%% Write - Buffered write, flushed in the background
+-export([
+    buffer_start/1,
+    buffer_start/2,
+    append/3,
+    buffer_flush/1,
+    buffer_close/1
+]).
+
+%% Logging of the Rust layer
+-export([
+    set_log_handler/1,
+    set_log_handler/2,
+    set_log_level/1
 ]).
 
 -export_type([
     client/0,
-    opts/0
+    opts/0,
+    endpoint_status/0,
+    ping_info/0,
+    stats/0,
+    stream_stats/0,
+    spool_opts/0,
+    spool_status/0,
+    in_flight_limits/0,
+    dedup_opts/0,
+    histogram/0,
+    insert_opts/0,
+    query_opts/0,
+    stream_write_opts/0,
+    stream_start_opts/0,
+    trace_opts/0,
+    buffered_writer/0,
+    buffer_opts/0,
+    row_error/0,
+    credentials/0,
+    tls_opts/0,
+    retry_opts/0,
+    retry_info/0,
+    insert_info/0,
+    column_info/0,
+    log_level/0,
+    log_handler_opts/0
 ]).
 
 %% ===================================================================
@@ -59,15 +123,44 @@
     endpoints := [binary()],
     dbname := binary(),
     username => binary(),
-    password => binary(),
+    password => secret(),
+    token => secret(),
     ts_column => binary(),
     ttl => binary(),
+    default_timestamp => default_timestamp(),
+    coercion => coercion(),
+    schema_hints => schema_hints(),
     tls => boolean(),
     verify => verify_peer | verify_none | binary(),
-    ca_cert => binary(),
-    client_cert => binary(),
-    client_key => binary(),
+    ca_cert => pem(),
+    client_cert => pem(),
+    client_key => pem(),
     cipher_suites => [binary()],
+    fips_mode => auto | required | off,
+    proxy => proxy_opts(),
+    connect_timeout => pos_integer(),
+    request_timeout => pos_integer(),
+    keepalive_interval => pos_integer(),
+    keepalive_timeout => pos_integer(),
+    keepalive_while_idle => boolean(),
+    tcp_keepalive => pos_integer(),
+    max_send_message_size => pos_integer(),
+    max_recv_message_size => pos_integer(),
+    initial_stream_window_size => pos_integer(),
+    initial_connection_window_size => pos_integer(),
+    compression => none | gzip | zstd,
+    %% OTLP/gRPC collector to export the spans of traced calls to
+    otlp_endpoint => binary(),
+    balance => round_robin | least_latency,
+    health_check_interval => pos_integer() | infinity,
+    health_check_timeout => pos_integer(),
+    retry => false | retry_opts(),
+    spool => spool_opts(),
+    %% Inserts, stream writes and buffered rows in flight on each connection
+    max_in_flight => in_flight_limits(),
+    %% Writes in flight on each stream
+    stream_max_in_flight => in_flight_limits(),
+    dedup => dedup_opts(),
     pool_name => pool_name(),
     pool_size => pool_size(),
     pool_type => pool_type(),
@@ -79,8 +172,242 @@
     pool_type := pool_type(),
     conn_opts := opts()
 }.
--type stream_client() :: {stream_client, client(), table()}.
+-type proxy_opts() :: #{
+    %% `http://Host:Port' (HTTP CONNECT) or `socks5://Host:Port'
+    url := binary(),
+    username => binary(),
+    password => binary(),
+    %% Hosts reached directly: exact names, `.domain' suffixes, or `*'
+    no_proxy => [binary()]
+}.
+%% A file path, or the PEM content itself
+-type pem() :: binary().
+-type tls_opts() :: #{
+    verify => verify_peer | verify_none | binary(),
+    ca_cert => pem(),
+    client_cert => pem(),
+    client_key => pem(),
+    cipher_suites => [binary()]
+}.
+%% A zero-arity fun is called on (re)connect, to keep the secret out of the process state
+-type secret() :: binary() | fun(() -> binary()).
+-type credentials() ::
+    #{username := binary(), password := secret()}
+    | #{token := secret()}.
+-type retry_opts() :: #{
+    max_attempts => pos_integer(),
+    initial_backoff => non_neg_integer(),
+    max_backoff => non_neg_integer(),
+    jitter => float(),
+    retry_on => [retry_status()],
+    deadline => pos_integer()
+}.
+-type retry_status() ::
+    unavailable
+    | deadline_exceeded
+    | resource_exhausted
+    | aborted
+    | cancelled
+    | internal
+    | unknown.
+%% Reported by writes when a retry policy is configured
+-type retry_info() :: #{attempts := non_neg_integer()}.
+%% `attempts' with a retry policy, unless a resend was skipped without being sent;
+%% `deduplicated' with an `idempotency_key'
+-type insert_info() :: #{
+    attempts => non_neg_integer(),
+    deduplicated => boolean()
+}.
+-type insert_opts() :: #{
+    partial_write => boolean(),
+    schema_hints => schema_hints(),
+    %% Resends of a batch written within the dedup window are skipped
+    idempotency_key => binary(),
+    %% Write to another database than the client's, not allowed with a `spool'
+    db => binary(),
+    traceparent => binary(),
+    tracestate => binary()
+}.
+-type query_opts() :: #{
+    %% Run in another database than the client's
+    db => binary(),
+    traceparent => binary(),
+    tracestate => binary()
+}.
+-type stream_start_opts() :: #{db => binary()}.
+-type stream_write_opts() :: trace_opts().
+%% W3C trace context the call continues
+-type trace_opts() :: #{
+    traceparent => binary(),
+    tracestate => binary()
+}.
+%% Rows are flushed per table on whichever limit is reached first
+-type buffer_opts() :: #{
+    %% Default 5000
+    max_rows => pos_integer(),
+    %% Encoded size of the rows, default 4 MiB
+    max_bytes => pos_integer(),
+    %% Age of the oldest row in milliseconds, default 1000
+    linger => pos_integer(),
+    %% Receives `{greptimedb_rs_flush, Table, {ok, affected()} | {error, Reason}}'
+    notify => pid()
+}.
+-type buffered_writer() :: reference().
+-type schema_hints() :: #{binary() => column_type() | {column_type(), tag | field}}.
+-type column_type() ::
+    boolean
+    | int8
+    | int16
+    | int32
+    | int64
+    | uint8
+    | uint16
+    | uint32
+    | uint64
+    | float32
+    | float64
+    | string
+    | binary
+    | json
+    | date
+    | datetime
+    | timestamp_second
+    | timestamp_millisecond
+    | timestamp_microsecond
+    | timestamp_nanosecond.
+%% A column as reported by `describe_table/2'
+-type column_info() :: #{
+    name := binary(),
+    %% As named by the server, such as `<<"Float64">>' or `<<"TimestampMillisecond">>'
+    data_type := binary(),
+    semantic_type := tag | field | timestamp,
+    nullable := boolean(),
+    default := binary() | undefined,
+    %% Unknown kinds of index are reported by their constraint name
+    indexes := [time_index | primary_key | inverted | fulltext | skipping | binary()]
+}.
+%% {RowIndex (1-based), Column, Reason}
+-type row_error() :: {pos_integer(), binary(), term()}.
+-type endpoint_status() :: #{
+    endpoint := binary(),
+    status := up | down | unknown,
+    latency_us := non_neg_integer() | undefined,
+    last_error := binary() | undefined,
+    last_check := integer() | undefined,
+    %% Of the last TLS handshake, by IANA name
+    cipher_suite := binary() | undefined,
+    %% The handshakes run on a FIPS crypto provider
+    fips := boolean()
+}.
+-type stats() :: #{
+    inserts := non_neg_integer(),
+    queries := non_neg_integer(),
+    deletes := non_neg_integer(),
+    rows_written := non_neg_integer(),
+    %% Left out of `partial_write' inserts
+    rows_rejected := non_neg_integer(),
+    %% Encoded size of the insert requests before compression, counting retries
+    bytes_sent := non_neg_integer(),
+    retries := non_neg_integer(),
+    schema_fetches := non_neg_integer(),
+    errors := error_counts(),
+    in_flight := non_neg_integer(),
+    %% Estimated size of the rows in flight
+    in_flight_bytes := non_neg_integer(),
+    %% Calls failed with `overloaded'
+    overloaded := non_neg_integer(),
+    %% Resends skipped by their idempotency key, for the database of the connection
+    deduplicated := non_neg_integer(),
+    streams_open := non_neg_integer(),
+    insert_latency_us := histogram(),
+    query_latency_us := histogram(),
+    delete_latency_us := histogram(),
+    schema_fetch_latency_us := histogram(),
+    compression := none | gzip | zstd,
+    %% Keyed by `{Db, Table}' for streams started with a `db'
+    streams := #{table() | {binary(), table()} => stream_stats()}
+}.
+%% Inserts failing on an unreachable server are written to `dir' and replayed in order
+%% once it is back. Each connection of the pool spools to a subdirectory of its own.
+-type spool_opts() :: #{
+    dir := binary(),
+    %% Size of the spool on disk, default 1 GiB
+    max_bytes => pos_integer(),
+    %% Size of each segment file, default 16 MiB
+    segment_bytes => pos_integer(),
+    %% What makes room when full, default `drop_oldest'
+    eviction => drop_oldest | reject_new,
+    %% Milliseconds between replay attempts, default 1000
+    replay_interval => pos_integer()
+}.
+%% Idempotency keys acknowledged within `window' milliseconds are remembered, default
+%% 300000, up to `capacity' keys, default 100000, forgetting the least recently used first.
+%% Shared by the connections to the same endpoints and database.
+-type dedup_opts() :: #{
+    window => pos_integer(),
+    capacity => pos_integer()
+}.
+%% Calls over a limit wait up to `timeout' milliseconds for room, then fail with
+%% `{error, overloaded}'. A call exceeding a limit on its own goes through when nothing
+%% else is in flight.
+-type in_flight_limits() :: #{
+    requests => pos_integer(),
+    %% Estimated size of the rows
+    bytes => pos_integer(),
+    %% Default 0, failing at once
+    timeout => non_neg_integer()
+}.
+-type spool_status() :: #{
+    dir := binary(),
+    %% Waiting to be replayed
+    records := non_neg_integer(),
+    bytes := non_neg_integer(),
+    segments := pos_integer(),
+    spooled := non_neg_integer(),
+    replayed := non_neg_integer(),
+    %% Deleted by `drop_oldest' before being replayed
+    evicted := non_neg_integer(),
+    %% Rejected by the server on replay
+    discarded := non_neg_integer(),
+    %% Unreadable, failing their checksum
+    corrupt := non_neg_integer()
+}.
+-type stream_stats() :: #{
+    writes := non_neg_integer(),
+    rows_written := non_neg_integer(),
+    retries := non_neg_integer(),
+    errors := error_counts(),
+    in_flight := non_neg_integer(),
+    in_flight_bytes := non_neg_integer(),
+    overloaded := non_neg_integer(),
+    write_latency_us := histogram()
+}.
+-type error_counts() :: #{
+    conversion := non_neg_integer(),
+    timeout := non_neg_integer(),
+    unavailable := non_neg_integer(),
+    server := non_neg_integer()
+}.
+%% Cumulative counts per upper bound in microseconds, as Prometheus histograms
+-type histogram() :: #{
+    buckets := [{non_neg_integer() | infinity, non_neg_integer()}],
+    count := non_neg_integer(),
+    sum := non_neg_integer()
+}.
+-type ping_info() :: #{
+    latency_us := non_neg_integer(),
+    version := binary() | undefined
+}.
+-type log_level() :: none | error | warning | info | debug | trace.
+-type log_handler_opts() :: #{
+    %% Records over the limit are dropped and their count reported, default 100
+    max_per_second => pos_integer()
+}.
+-type stream_client() :: {stream_client, client(), table() | {binary(), table()}}.
+-type default_timestamp() :: now | error | {column, binary()}.
+-type coercion() :: strict | widen | stringify | null_on_error.
 -type table() :: binary().
+-type affected() :: non_neg_integer() | spooled.
 -type sql() :: binary().
 -type result() :: term().
 -type reason() :: term() | binary().
@@ -134,6 +461,74 @@ stop_client(?pool_name(PoolName)) ->
 stop_client(PoolName) ->
     ecpool:stop_sup_pool(PoolName).
 
+%% @doc Health of each configured endpoint, as seen by one connection of the pool.
+-spec connection_status(client()) -> {ok, [endpoint_status()]} | {error, reason()}.
+connection_status(Client) ->
+    call_sync(Client, ?cmd_connection_status, []).
+
+%% @doc Replace the credentials of every connection of the pool, without reconnecting.
+%% Streams already started keep their credentials. Connections re-established later
+%% use the `start_client/1' options again, pass the secret as a fun to pick up rotations.
+-spec update_auth(client(), credentials()) -> ok | {error, reason()}.
+update_auth(Client, Creds) ->
+    call_all(Client, ?cmd_update_auth, [Creds]).
+
+%% @doc Rebuild the TLS channels of every connection of the pool, reading the
+%% certificates again. `TlsOpts' (`verify', `ca_cert', `client_cert', `client_key',
+%% `cipher_suites') override the ones given to `start_client/1'; pass `#{}' to
+%% reload certificates rotated on disk. Streams already started keep their channel.
+-spec reload_tls(client(), tls_opts()) -> ok | {error, reason()}.
+reload_tls(Client, TlsOpts) ->
+    call_all(Client, ?cmd_reload_tls, [TlsOpts]).
+
+%% @doc Cheap round-trip to the server through one connection of the pool.
+-spec ping(client()) -> {ok, ping_info()} | {error, timeout | reason()}.
+ping(Client) ->
+    ping(Client, 5000).
+
+-spec ping(client(), pos_integer()) -> {ok, ping_info()} | {error, timeout | reason()}.
+ping(Client, Timeout) ->
+    call_sync(Client, ?cmd_ping, [Timeout]).
+
+%% @doc Counters and latency histograms of every connection of the pool, with those
+%% of its streams by table.
+-spec stats(client()) -> {ok, [stats()]} | {error, reason()}.
+stats(Client) ->
+    Results = call_each(Client, ?cmd_stats, []),
+    case [Err || {error, _} = Err <- Results] of
+        [] -> {ok, [Stats || {ok, Stats} <- Results]};
+        [Err | _] -> Err
+    end.
+
+%% @doc Backlog and counters of the spool of every connection of the pool.
+-spec spool_status(client()) -> {ok, [spool_status()]} | {error, reason()}.
+spool_status(Client) ->
+    Results = call_each(Client, ?cmd_spool_status, []),
+    case [Err || {error, _} = Err <- Results] of
+        [] -> {ok, [Status || {ok, Status} <- Results]};
+        [Err | _] -> Err
+    end.
+
+%% ===================================================================
+%% Logging
+%% ===================================================================
+
+%% @doc Send the log records of the Rust layer (ingester, gRPC, TLS) to `Handler' as
+%% `{greptimedb_rs_log, Level, Message, Meta}', or stop with `undefined'.
+%% `greptimedb_rs_logger' forwards them to `logger'.
+-spec set_log_handler(pid() | undefined) -> ok | {error, reason()}.
+set_log_handler(Handler) ->
+    set_log_handler(Handler, #{}).
+
+-spec set_log_handler(pid() | undefined, log_handler_opts()) -> ok | {error, reason()}.
+set_log_handler(Handler, Opts) ->
+    greptimedb_rs_nif:set_log_handler(Handler, Opts).
+
+%% @doc Most verbose level of the records forwarded, `warning' by default.
+-spec set_log_level(log_level()) -> ok | {error, reason()}.
+set_log_level(Level) ->
+    greptimedb_rs_nif:set_log_level(Level).
+
 %% ===================================================================
 %% Write - Batch Write
 %% ===================================================================
@@ -142,12 +537,47 @@ stop_client(PoolName) ->
 %% Picks a connection from the pool to do the write operation.
 -spec insert(client(), binary(), [map()]) -> {ok, integer()} | {error, reason()}.
 insert(Client, Table, Rows) ->
-    call_sync(Client, ?cmd_insert, [Table, Rows]).
+    insert(Client, Table, Rows, #{}).
+
+%% @doc Batch write data with per-call options (blocking).
+%% With `partial_write => true', rows that fail to convert are left out of the
+%% write and reported as `{ok, Affected, RowErrors}'.
+%% With a `retry' policy configured, a `retry_info()' map is appended to the
+%% result and to the error reason.
+%% With an `idempotency_key', an `insert_info()' map tells whether the batch was
+%% written already, the result of that write being returned. A resend while it is still
+%% in flight fails with `{error, in_progress}'.
+%% With a `spool', rows the server could not take are reported as `spooled' instead
+%% of the affected count.
+%% Returns `{error, overloaded}' when `max_in_flight' is reached.
+-spec insert(client(), binary(), [map()], insert_opts()) ->
+    {ok, affected()}
+    | {ok, affected(), [row_error()]}
+    | {ok, affected(), insert_info()}
+    | {ok, affected(), [row_error()], insert_info()}
+    | {error, reason()}.
+insert(Client, Table, Rows, Opts) ->
+    call_sync(Client, ?cmd_insert, [Table, Rows, Opts]).
 
 %% @doc Batch write data (asynchronous).
 -spec insert_async(client(), binary(), [map()], callback()) -> {ok, pid()}.
 insert_async(Client, Table, Rows, ResultCallback) ->
-    call_async(Client, ?cmd_insert, [Table, Rows], ResultCallback).
+    insert_async(Client, Table, Rows, #{}, ResultCallback).
+
+%% @doc Batch write data with per-call options (asynchronous).
+-spec insert_async(client(), binary(), [map()], insert_opts(), callback()) -> {ok, pid()}.
+insert_async(Client, Table, Rows, Opts, ResultCallback) ->
+    call_async(Client, ?cmd_insert, [Table, Rows, Opts], ResultCallback).
+
+%% @doc Delete rows by their tags and timestamp (blocking), fields are ignored.
+%% With a `retry' policy configured, a `retry_info()' map is appended to the
+%% result and to the error reason.
+-spec delete(client(), binary(), [map()]) ->
+    {ok, non_neg_integer()}
+    | {ok, non_neg_integer(), retry_info()}
+    | {error, reason()}.
+delete(Client, Table, Rows) ->
+    call_sync(Client, ?cmd_delete, [Table, Rows]).
 
 %% ===================================================================
 %% Write - Execute Query
@@ -156,12 +586,43 @@ insert_async(Client, Table, Rows, ResultCallback) ->
 %% @doc Execute SQL query (blocking).
 -spec query(client(), sql()) -> {ok, result()} | {error, reason()}.
 query(Client, Sql) ->
-    call_sync(Client, ?cmd_execute, [Sql]).
+    query(Client, Sql, #{}).
+
+%% @doc Execute SQL query with per-call options (blocking).
+-spec query(client(), sql(), query_opts()) -> {ok, result()} | {error, reason()}.
+query(Client, Sql, Opts) ->
+    call_sync(Client, ?cmd_execute, [Sql, Opts]).
 
 %% @doc Execute SQL query (asynchronous).
 -spec query_async(client(), sql(), callback()) -> {ok, pid()}.
 query_async(Client, Sql, ResultCallback) ->
-    call_async(Client, ?cmd_execute, [Sql], ResultCallback).
+    query_async(Client, Sql, #{}, ResultCallback).
+
+%% @doc Execute SQL query with per-call options (asynchronous).
+-spec query_async(client(), sql(), query_opts(), callback()) -> {ok, pid()}.
+query_async(Client, Sql, Opts, ResultCallback) ->
+    call_async(Client, ?cmd_execute, [Sql, Opts], ResultCallback).
+
+%% ===================================================================
+%% Schema introspection
+%% ===================================================================
+
+-spec list_databases(client()) -> {ok, [binary()]} | {error, reason()}.
+list_databases(Client) ->
+    call_sync(Client, ?cmd_list_databases, []).
+
+-spec list_tables(client(), binary()) -> {ok, [table()]} | {error, reason()}.
+list_tables(Client, Db) ->
+    call_sync(Client, ?cmd_list_tables, [Db]).
+
+%% @doc Columns of a table of the client's database, in table order.
+-spec describe_table(client(), table()) -> {ok, [column_info()]} | {error, reason()}.
+describe_table(Client, Table) ->
+    call_sync(Client, ?cmd_describe_table, [Table]).
+
+-spec table_exists(client(), table()) -> {ok, boolean()} | {error, reason()}.
+table_exists(Client, Table) ->
+    call_sync(Client, ?cmd_table_exists, [Table]).
 
 %% ===================================================================
 %% Write - Streaming Write
@@ -171,21 +632,31 @@ query_async(Client, Sql, ResultCallback) ->
 %% Returns a StreamClient handle that binds the Table context to the Client.
 %% It attempts to initialize the stream on all workers (best effort).
 -spec stream_start(client(), binary(), map()) -> {ok, stream_client()}.
-stream_start(?pool_name(PoolName) = Client, Table, FirstRow) ->
+stream_start(Client, Table, FirstRow) ->
+    stream_start(Client, Table, FirstRow, #{}).
+
+%% @doc Start a stream with options, `db' streaming to another database than the client's.
+-spec stream_start(client(), binary(), map(), stream_start_opts()) -> {ok, stream_client()}.
+stream_start(?pool_name(PoolName) = Client, Table, FirstRow, Opts) ->
     Workers = ecpool:workers(PoolName),
     %% Attempt to pre-warm all workers. Ignore errors/ignored returns.
     lists:foreach(
         fun({_Name, Worker}) ->
             try
                 {ok, Conn} = ecpool_worker:client(Worker),
-                greptimedb_rs_sock:sync_command(Conn, ?cmd_stream_start, [Table, FirstRow])
+                greptimedb_rs_sock:sync_command(Conn, ?cmd_stream_start, [Table, FirstRow, Opts])
             catch
                 _:_ -> ok
             end
         end,
         Workers
     ),
-    {ok, {stream_client, Client, Table}}.
+    Key =
+        case Opts of
+            #{db := Db} -> {Db, Table};
+            _ -> Table
+        end,
+    {ok, {stream_client, Client, Key}}.
 
 %% @doc Stop the stream for a specific table.
 %% Releases stream resources on all workers in the pool.
@@ -207,14 +678,59 @@ stream_close({stream_client, ?pool_name(PoolName), Table}) ->
 %% @doc Write data to the stream (blocking).
 %% Uses ecpool to pick a connection from the pool.
 %% The worker lazily initializes the stream writer if needed.
--spec stream_write(stream_client(), [map()]) -> ok | {error, term()}.
-stream_write({stream_client, Client, Table}, Rows) ->
-    call_sync(Client, ?cmd_stream_write, [Table, Rows]).
+-spec stream_write(stream_client(), [map()]) -> ok | {ok, retry_info()} | {error, term()}.
+stream_write(StreamClient, Rows) ->
+    stream_write(StreamClient, Rows, #{}).
+
+%% @doc Write data to the stream with per-call options (blocking).
+-spec stream_write(stream_client(), [map()], stream_write_opts()) ->
+    ok | {ok, retry_info()} | {error, term()}.
+stream_write({stream_client, Client, Table}, Rows, Opts) ->
+    call_sync(Client, ?cmd_stream_write, [Table, Rows, Opts]).
 
 %% @doc Write data to the stream (asynchronous).
 -spec stream_write_async(stream_client(), [map()], callback()) -> {ok, pid()}.
-stream_write_async({stream_client, Client, Table}, Rows, Callback) ->
-    call_async(Client, ?cmd_stream_write, [Table, Rows], Callback).
+stream_write_async(StreamClient, Rows, Callback) ->
+    stream_write_async(StreamClient, Rows, #{}, Callback).
+
+%% @doc Write data to the stream with per-call options (asynchronous).
+-spec stream_write_async(stream_client(), [map()], stream_write_opts(), callback()) ->
+    {ok, pid()}.
+stream_write_async({stream_client, Client, Table}, Rows, Opts, Callback) ->
+    call_async(Client, ?cmd_stream_write, [Table, Rows, Opts], Callback).
+
+%% ===================================================================
+%% Write - Buffered Write
+%% ===================================================================
+
+%% @doc Start a writer buffering rows on one connection of the pool.
+-spec buffer_start(client()) -> {ok, buffered_writer()} | {error, reason()}.
+buffer_start(Client) ->
+    buffer_start(Client, #{}).
+
+-spec buffer_start(client(), buffer_opts()) -> {ok, buffered_writer()} | {error, reason()}.
+buffer_start(Client, Opts) ->
+    call_sync(Client, ?cmd_buffer_start, [Opts]).
+
+%% @doc Add rows to the buffer of their table, without waiting for them to be written.
+%% Rows that don't convert fail the call, flush failures are only reported to `notify'.
+-spec append(buffered_writer(), table(), [map()]) -> ok | {error, reason()}.
+append(Writer, Table, Rows) ->
+    try
+        greptimedb_rs_nif:append(Writer, Table, Rows)
+    catch
+        error:Reason -> {error, {nif_error, Reason}}
+    end.
+
+%% @doc Flush every table now, the results are reported to `notify'.
+-spec buffer_flush(buffered_writer()) -> ok | {error, reason()}.
+buffer_flush(Writer) ->
+    greptimedb_rs_nif:buffer_flush(Writer).
+
+%% @doc Flush every table and wait for the flushes to complete. Appends fail afterwards.
+-spec buffer_close(buffered_writer()) -> ok.
+buffer_close(Writer) ->
+    greptimedb_rs_nif:buffer_close(Writer).
 
 %% ===================================================================
 %% Helpers
@@ -228,6 +744,27 @@ call_sync(?pool_name(PoolName), Cmd, Args) ->
         end
     ).
 
+%% Run the command on every connection of the pool, returning the first error if any.
+call_all(Client, Cmd, Args) ->
+    case [Err || {error, _} = Err <- call_each(Client, Cmd, Args)] of
+        [] -> ok;
+        [Err | _] -> Err
+    end.
+
+%% Run the command on every connection of the pool, returning all results.
+call_each(?pool_name(PoolName), Cmd, Args) ->
+    lists:map(
+        fun({_Name, Worker}) ->
+            try
+                {ok, Conn} = ecpool_worker:client(Worker),
+                greptimedb_rs_sock:sync_command(Conn, Cmd, Args)
+            catch
+                _:Reason -> {error, Reason}
+            end
+        end,
+        ecpool:workers(PoolName)
+    ).
+
 call_async(?pool_name(PoolName), Cmd, Args, Callback) ->
     ecpool:with_client(
         PoolName,
@@ -0,0 +1,99 @@
+%%--------------------------------------------------------------------
+%% Copyright (c) 2025 EMQ Technologies Co., Ltd. All Rights Reserved.
+%%
+%% Licensed under the Apache License, Version 2.0 (the "License");
+%% you may not use this file except in compliance with the License.
+%% You may obtain a copy of the License at
+%%
+%%     http://www.apache.org/licenses/LICENSE-2.0
+%%
+%% Unless required by applicable law or agreed to in writing, software
+%% distributed under the License is distributed on an "AS IS" BASIS,
+%% WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+%% See the License for the specific language governing permissions and
+%% limitations under the License.
+%%--------------------------------------------------------------------
+
+%% Forwards the log records of the Rust layer to `logger'.
+-module(greptimedb_rs_logger).
+
+-behavior(gen_server).
+
+-export([
+    start_link/0,
+    start_link/1
+]).
+
+%% gen_server callbacks
+-export([
+    init/1,
+    handle_call/3,
+    handle_info/2,
+    handle_cast/2,
+    terminate/2
+]).
+
+-type opts() :: #{
+    level => greptimedb_rs:log_level(),
+    max_per_second => pos_integer()
+}.
+
+%% ================================================================================
+%% API
+
+-spec start_link() -> {ok, pid()} | {error, term()}.
+start_link() ->
+    start_link(#{}).
+
+-spec start_link(opts()) -> {ok, pid()} | {error, term()}.
+start_link(Opts) ->
+    gen_server:start_link({local, ?MODULE}, ?MODULE, Opts, []).
+
+%% ================================================================================
+
+init(Opts) ->
+    process_flag(trap_exit, true),
+    Result =
+        case maps:find(level, Opts) of
+            {ok, Level} -> greptimedb_rs:set_log_level(Level);
+            error -> ok
+        end,
+    case Result of
+        ok ->
+            case greptimedb_rs:set_log_handler(self(), maps:remove(level, Opts)) of
+                ok -> {ok, #{}};
+                {error, Reason} -> {stop, Reason}
+            end;
+        {error, Reason} ->
+            {stop, Reason}
+    end.
+
+handle_call(_Req, _From, State) ->
+    {reply, {error, unexpected_call}, State}.
+
+handle_cast(_Msg, State) ->
+    {noreply, State}.
+
+handle_info({greptimedb_rs_log, Level, Message, Meta}, State) ->
+    logger:log(Level, "~ts", [Message], metadata(Meta)),
+    {noreply, State};
+handle_info(_Info, State) ->
+    {noreply, State}.
+
+terminate(_Reason, _State) ->
+    _ = greptimedb_rs:set_log_handler(undefined),
+    ok.
+
+%% ================================================================================
+%% Helpers
+
+metadata(Meta) ->
+    maps:fold(
+        fun
+            (_Key, undefined, Acc) -> Acc;
+            (file, File, Acc) -> Acc#{file => binary_to_list(File)};
+            (Key, Value, Acc) -> Acc#{Key => Value}
+        end,
+        #{domain => [greptimedb_rs, nif]},
+        Meta
+    ).
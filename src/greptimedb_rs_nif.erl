@@ -19,13 +19,34 @@
 -export([
     connect/1,
     disconnect/1,
+    connection_status/1,
+    update_auth/2,
+    reload_tls/2,
+    ping/2,
+    stats/1,
+    spool_status/1,
     execute/2,
+    execute/3,
+    list_databases/1,
+    list_tables/2,
+    describe_table/2,
+    table_exists/2,
     insert/3,
+    insert/4,
+    delete/3,
     stream_start/3,
+    stream_start/4,
     stream_write/2,
+    stream_write/3,
     stream_close/1,
+    buffer_start/2,
+    append/3,
+    buffer_flush/1,
+    buffer_close/1,
     fips_status/0,
-    cached_fips_status/0
+    cached_fips_status/0,
+    set_log_handler/2,
+    set_log_level/1
 ]).
 
 -export([init/0]).
@@ -48,24 +69,87 @@ connect(_Opts) ->
 disconnect(_Client) ->
     not_loaded(?LINE).
 
-execute(_Client, _Sql) ->
+connection_status(_Client) ->
     not_loaded(?LINE).
 
-insert(_Client, _Table, _Rows) ->
+update_auth(_Client, _Creds) ->
     not_loaded(?LINE).
 
-stream_start(_Client, _Table, _FirstRow) ->
+reload_tls(_Client, _TlsOpts) ->
     not_loaded(?LINE).
 
-stream_write(_Writer, _Rows) ->
+ping(_Client, _Timeout) ->
+    not_loaded(?LINE).
+
+stats(_Client) ->
+    not_loaded(?LINE).
+
+spool_status(_Client) ->
+    not_loaded(?LINE).
+
+execute(Client, Sql) ->
+    execute(Client, Sql, #{}).
+
+execute(_Client, _Sql, _Opts) ->
+    not_loaded(?LINE).
+
+list_databases(_Client) ->
+    not_loaded(?LINE).
+
+list_tables(_Client, _Db) ->
+    not_loaded(?LINE).
+
+describe_table(_Client, _Table) ->
+    not_loaded(?LINE).
+
+table_exists(_Client, _Table) ->
+    not_loaded(?LINE).
+
+insert(Client, Table, Rows) ->
+    insert(Client, Table, Rows, #{}).
+
+insert(_Client, _Table, _Rows, _Opts) ->
+    not_loaded(?LINE).
+
+delete(_Client, _Table, _Rows) ->
+    not_loaded(?LINE).
+
+stream_start(Client, Table, FirstRow) ->
+    stream_start(Client, Table, FirstRow, #{}).
+
+stream_start(_Client, _Table, _FirstRow, _Opts) ->
+    not_loaded(?LINE).
+
+stream_write(Writer, Rows) ->
+    stream_write(Writer, Rows, #{}).
+
+stream_write(_Writer, _Rows, _Opts) ->
     not_loaded(?LINE).
 
 stream_close(_Writer) ->
     not_loaded(?LINE).
 
+buffer_start(_Client, _Opts) ->
+    not_loaded(?LINE).
+
+append(_Writer, _Table, _Rows) ->
+    not_loaded(?LINE).
+
+buffer_flush(_Writer) ->
+    not_loaded(?LINE).
+
+buffer_close(_Writer) ->
+    not_loaded(?LINE).
+
 fips_status() ->
     not_loaded(?LINE).
 
+set_log_handler(_Handler, _Opts) ->
+    not_loaded(?LINE).
+
+set_log_level(_Level) ->
+    not_loaded(?LINE).
+
 %% =================================================================================================
 %% Helpers
 
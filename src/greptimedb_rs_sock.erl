@@ -64,7 +64,7 @@ sync_command(Conn, Command, Args) ->
 connect(Args) when is_list(Args) ->
     case proplists:get_value(conn_opts, Args) of
         undefined -> {ok, #state{client = undefined, opts = undefined, writers = #{}}};
-        Opts -> connect(Opts)
+        Opts -> connect(spool_per_worker(Opts, proplists:get_value(ecpool_worker_id, Args)))
     end;
 connect(Opts) when is_map(Opts) ->
     %% Start the connection gen_server process
@@ -73,7 +73,7 @@ connect(Opts) when is_map(Opts) ->
 %% ================================================================================
 
 init([Opts0]) ->
-    Opts = unwrap_password(Opts0),
+    Opts = unwrap_secrets(Opts0),
     case apply_nif(?cmd_connect, [Opts]) of
         {ok, ClientRef} ->
             log_connect_info(Opts0),
@@ -88,7 +88,7 @@ handle_call(
     _From,
     State = #state{client = undefined}
 ) ->
-    Opts = unwrap_password(Opts0),
+    Opts = unwrap_secrets(Opts0),
     case apply_nif(?cmd_connect, [Opts]) of
         {ok, ClientRef} = Ok when is_reference(ClientRef) ->
             {reply, Ok, State#state{client = ClientRef, opts = Opts0}};
@@ -97,25 +97,62 @@ handle_call(
     end;
 handle_call(?REQ(?cmd_connect, _), _From, State) ->
     {reply, {ok, State#state.client}, State};
-handle_call(?REQ(?cmd_execute, [Sql]), _From, State = ?client_ref(ClientRef)) ->
-    Result = apply_nif(?cmd_execute, [ClientRef, Sql]),
+handle_call(?REQ(?cmd_execute, [Sql]), From, State) ->
+    handle_call(?REQ(?cmd_execute, [Sql, #{}]), From, State);
+handle_call(?REQ(?cmd_execute, [Sql, Opts]), _From, State = ?client_ref(ClientRef)) ->
+    Result = apply_nif(?cmd_execute, [ClientRef, Sql, Opts]),
     {reply, Result, State};
-handle_call(?REQ(?cmd_insert, [Table, Rows]), _From, State = ?client_ref(ClientRef)) ->
-    Result = apply_nif(?cmd_insert, [ClientRef, Table, Rows]),
+handle_call(?REQ(?cmd_insert, [Table, Rows]), From, State) ->
+    handle_call(?REQ(?cmd_insert, [Table, Rows, #{}]), From, State);
+handle_call(?REQ(?cmd_insert, [Table, Rows, Opts]), _From, State = ?client_ref(ClientRef)) ->
+    Result = apply_nif(?cmd_insert, [ClientRef, Table, Rows, Opts]),
     {reply, Result, State};
+handle_call(?REQ(?cmd_delete, [Table, Rows]), _From, State = ?client_ref(ClientRef)) ->
+    Result = apply_nif(?cmd_delete, [ClientRef, Table, Rows]),
+    {reply, Result, State};
+handle_call(?REQ(?cmd_update_auth, [Creds]), _From, State = ?client_ref(ClientRef)) ->
+    Result = apply_nif(?cmd_update_auth, [ClientRef, unwrap_secrets(Creds)]),
+    {reply, Result, State};
+handle_call(?REQ(?cmd_reload_tls, [TlsOpts]), _From, State = ?client_ref(ClientRef)) ->
+    Result = apply_nif(?cmd_reload_tls, [ClientRef, TlsOpts]),
+    {reply, Result, State};
+handle_call(?REQ(?cmd_stats, []), _From, State = #state{client = ClientRef, writers = Writers}) ->
+    Result =
+        case apply_nif(?cmd_stats, [ClientRef]) of
+            {ok, Stats} ->
+                Streams = maps:fold(
+                    fun(Table, WriterRef, Acc) ->
+                        case apply_nif(?cmd_stats, [WriterRef]) of
+                            {ok, StreamStats} -> Acc#{Table => StreamStats};
+                            _ -> Acc
+                        end
+                    end,
+                    #{},
+                    Writers
+                ),
+                {ok, Stats#{streams => Streams}};
+            Error ->
+                Error
+        end,
+    {reply, Result, State};
+handle_call(?REQ(?cmd_stream_start, [Table, FirstRow]), From, State) ->
+    handle_call(?REQ(?cmd_stream_start, [Table, FirstRow, #{}]), From, State);
 handle_call(
-    ?REQ(?cmd_stream_start, [Table, FirstRow]),
+    ?REQ(?cmd_stream_start, [Table, FirstRow, Opts]),
     _From,
     State = #state{client = ClientRef, writers = Writers}
 ) ->
-    case apply_nif(?cmd_stream_start, [ClientRef, Table, FirstRow]) of
+    case apply_nif(?cmd_stream_start, [ClientRef, Table, FirstRow, Opts]) of
         {ok, Ref} ->
-            {reply, {ok, Ref}, State#state{writers = Writers#{Table => Ref}}};
+            Key = stream_key(Table, Opts),
+            {reply, {ok, Ref}, State#state{writers = Writers#{Key => Ref}}};
         Error ->
             {reply, Error, State}
     end;
-handle_call(?REQ(?cmd_stream_write, [Table, Rows]), _From, State = #state{}) ->
-    Res = write_with_stream(Table, Rows, State),
+handle_call(?REQ(?cmd_stream_write, [Table, Rows]), From, State) ->
+    handle_call(?REQ(?cmd_stream_write, [Table, Rows, #{}]), From, State);
+handle_call(?REQ(?cmd_stream_write, [Table, Rows, Opts]), _From, State = #state{}) ->
+    Res = write_with_stream(Table, Rows, Opts, State),
     {reply, Res, State};
 handle_call(?REQ(?cmd_stream_close, [Table]), _From, State = #state{writers = Writers}) ->
     NewWriters =
@@ -147,18 +184,22 @@ handle_cast(_Msg, State) ->
     {noreply, State}.
 
 %% ===================================================================
+handle_info(?ASYNC_REQ(?cmd_insert, [Table, Rows], Callback), State) ->
+    handle_info(?ASYNC_REQ(?cmd_insert, [Table, Rows, #{}], Callback), State);
 handle_info(
-    ?ASYNC_REQ(?cmd_insert, [Table, Rows], {CallbackFun, CallBackArgs}),
+    ?ASYNC_REQ(?cmd_insert, [Table, Rows, Opts], {CallbackFun, CallBackArgs}),
     State = ?client_ref(ClientRef)
 ) ->
-    Res = apply_nif(?cmd_insert, [ClientRef, Table, Rows]),
+    Res = apply_nif(?cmd_insert, [ClientRef, Table, Rows, Opts]),
     _ = erlang:apply(CallbackFun, CallBackArgs ++ [Res]),
     {noreply, State};
+handle_info(?ASYNC_REQ(?cmd_stream_write, [Table, Rows], Callback), State) ->
+    handle_info(?ASYNC_REQ(?cmd_stream_write, [Table, Rows, #{}], Callback), State);
 handle_info(
-    ?ASYNC_REQ(?cmd_stream_write, [Table, Rows], {CallbackFun, CallBackArgs}),
+    ?ASYNC_REQ(?cmd_stream_write, [Table, Rows, Opts], {CallbackFun, CallBackArgs}),
     State = #state{}
 ) ->
-    Res = write_with_stream(Table, Rows, State),
+    Res = write_with_stream(Table, Rows, Opts, State),
     _ = erlang:apply(CallbackFun, CallBackArgs ++ [Res]),
     {noreply, State};
 handle_info(?ASYNC_REQ(Func, Args, {CallbackFun, CallBackArgs}), State = ?client_ref(ClientRef)) ->
@@ -197,23 +238,37 @@ apply_nif(Func, Args) ->
         exit:Reason -> {error, {nif_exit, Reason}}
     end.
 
-write_with_stream(Table, Rows, _State = #state{writers = Writers}) ->
+write_with_stream(Table, Rows, Opts, _State = #state{writers = Writers}) ->
     case Writers of
         #{Table := WriterRef} ->
-            apply_nif(?cmd_stream_write, [WriterRef, Rows]);
+            apply_nif(?cmd_stream_write, [WriterRef, Rows, Opts]);
         _ ->
             {error, no_writer}
     end.
 
-unwrap_password(#{password := Password} = Opts) ->
-    Opts#{password := do_unwrap_password(Password)};
-unwrap_password(Opts) ->
+%% Streams to another database than the connection's are told apart by it
+stream_key(Table, #{db := Db}) -> {Db, Table};
+stream_key(Table, _Opts) -> Table.
+
+%% Each connection of the pool spools to a directory of its own
+spool_per_worker(Opts = #{spool := Spool = #{dir := Dir}}, WorkerId) when is_integer(WorkerId) ->
+    Opts#{spool := Spool#{dir := filename:join(Dir, integer_to_list(WorkerId))}};
+spool_per_worker(Opts, _WorkerId) ->
     Opts.
 
-do_unwrap_password(Password) when is_function(Password, 0) ->
-    do_unwrap_password(Password());
-do_unwrap_password(Password) ->
-    Password.
+unwrap_secrets(Opts) ->
+    maps:map(
+        fun
+            (Key, Secret) when Key =:= password; Key =:= token -> do_unwrap_secret(Secret);
+            (_Key, Value) -> Value
+        end,
+        Opts
+    ).
+
+do_unwrap_secret(Secret) when is_function(Secret, 0) ->
+    do_unwrap_secret(Secret());
+do_unwrap_secret(Secret) ->
+    Secret.
 
 log_connect_info(#{endpoints := Endpoints} = Opts) ->
     TlsEnabled = maps:get(tls, Opts, false),
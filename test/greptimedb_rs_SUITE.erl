@@ -14,32 +14,62 @@ all() ->
         {group, tcp},
         {group, tls},
         {group, tls_ciphers},
-        t_connect_with_auth
+        t_connect_with_auth,
+        t_update_auth,
+        t_log_handler
     ].
 
 groups() ->
     CommonTCs = [
         t_connect,
+        t_connection_status,
+        t_ping,
+        t_channel_options,
+        t_compression,
+        t_stats,
+        t_trace_context,
         t_metadata_queries,
         t_insert_sync,
         t_insert_sync_custom_ts_column,
         t_insert_sync_ttl_hint,
+        t_insert_sync_default_timestamp,
+        t_insert_sync_default_timestamp_column,
         t_insert_sync_existing_table,
         t_insert_sync_schema_conflict,
+        t_insert_sync_coercion,
+        t_insert_sync_partial_write,
+        t_insert_sync_infer_schema_from_all_rows,
+        t_insert_sync_schema_hints,
+        t_insert_sync_retry,
         t_query_sync,
         t_insert_async,
         t_insert_async_existing_table,
         t_insert_async_schema_conflict,
         t_query_async,
         t_stream_write,
-        t_stream_write_async
+        t_stream_write_async,
+        t_buffered_write,
+        t_spool,
+        t_in_flight_limits,
+        t_idempotent_insert,
+        t_delete,
+        t_introspection,
+        t_per_call_db
+    ],
+    TcpOnlyTCs = [
+        t_proxy_http_connect,
+        t_proxy_socks5
     ],
     TlsOnlyTCs = [
         t_connect_tls_without_client_certfiles,
         t_connect_tls_without_any_certfiles,
         t_connect_tls_verify_none_without_any_certfiles,
         t_connect_tls_verify_undefined_fallback_to_peer,
-        t_connect_tls_invalid_verify_returns_error
+        t_connect_tls_invalid_verify_returns_error,
+        t_connect_tls_pem,
+        t_connect_tls_config_errors,
+        t_reload_tls,
+        t_proxy_tls
     ],
     CipherTCs = [
         t_cipher_tls13_aes256_gcm,
@@ -50,11 +80,13 @@ groups() ->
         t_cipher_single_suite,
         t_cipher_empty_list_uses_defaults,
         t_cipher_all_invalid_returns_error,
-        t_cipher_partial_invalid_skips_bad,
-        t_fips_status_returns_boolean
+        t_cipher_partial_invalid_returns_error,
+        t_fips_status_returns_boolean,
+        t_fips_mode_required,
+        t_fips_mode_auto
     ],
     [
-        {tcp, [], CommonTCs},
+        {tcp, [], CommonTCs ++ TcpOnlyTCs},
         {tls, [], CommonTCs ++ TlsOnlyTCs},
         {tls_ciphers, [], CipherTCs}
     ].
@@ -161,6 +193,270 @@ t_connect(Config) ->
     ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
     ok = greptimedb_rs:stop_client(Client).
 
+t_connection_status(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    #{endpoints := [Endpoint]} = ConnOpts,
+    %% Nothing listens on this one
+    BadEndpoint = <<"127.0.0.1:1">>,
+    ConnOpts1 = ConnOpts#{
+        endpoints => [Endpoint, BadEndpoint],
+        pool_size => 1,
+        health_check_interval => 200,
+        health_check_timeout => 1000
+    },
+    ?assertMatch(
+        {error, _}, greptimedb_rs:start_client(ConnOpts1#{health_check_interval => 0})
+    ),
+    ?assertMatch({error, _}, greptimedb_rs:start_client(ConnOpts1#{health_check_timeout => 0})),
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts1),
+    timer:sleep(1500),
+
+    {ok, Statuses} = greptimedb_rs:connection_status(Client),
+    ?assertMatch(
+        [
+            #{endpoint := Endpoint, status := up, latency_us := L},
+            #{endpoint := BadEndpoint, status := down, last_error := E}
+        ] when is_integer(L) andalso is_binary(E),
+        Statuses
+    ),
+
+    %% Requests keep succeeding by avoiding the endpoint that is down
+    lists:foreach(
+        fun(_) -> ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)) end,
+        lists:seq(1, 4)
+    ),
+
+    ok = greptimedb_rs:stop_client(Client).
+
+t_ping(Config) ->
+    {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
+    {ok, #{latency_us := Latency, version := Version}} = greptimedb_rs:ping(Client, 5000),
+    ?assert(is_integer(Latency)),
+    ?assert(is_binary(Version)),
+    ok = greptimedb_rs:stop_client(Client),
+
+    %% Nothing listens on this one
+    {ok, BadClient} = greptimedb_rs:start_client(
+        (?conn_opts(Config))#{endpoints => [<<"127.0.0.1:1">>], health_check_interval => infinity}
+    ),
+    ?assertMatch({error, _}, greptimedb_rs:ping(BadClient, 1000)),
+    ok = greptimedb_rs:stop_client(BadClient).
+
+t_proxy_http_connect(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    #{endpoints := [Endpoint]} = ConnOpts,
+    ProxyPort = start_proxy(http, {<<"proxy_user">>, <<"proxy_pwd">>}),
+    Proxy = #{
+        url => <<"http://127.0.0.1:", (integer_to_binary(ProxyPort))/binary>>,
+        username => <<"proxy_user">>,
+        password => <<"proxy_pwd">>
+    },
+    ConnOpts1 = ConnOpts#{pool_size => 1, health_check_interval => infinity},
+
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts1#{proxy => Proxy}),
+    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+    receive
+        {proxied, http, Target} -> ?assertEqual(Endpoint, Target)
+    after 1000 -> ct:fail(not_proxied)
+    end,
+    ok = greptimedb_rs:stop_client(Client),
+
+    %% The proxy refuses the tunnel
+    {ok, BadClient} = greptimedb_rs:start_client(
+        ConnOpts1#{proxy => Proxy#{password => <<"wrong">>}}
+    ),
+    ?assertMatch({error, _}, greptimedb_rs:query(BadClient, <<"SELECT 1">>)),
+    ok = greptimedb_rs:stop_client(BadClient),
+
+    %% Hosts in no_proxy are reached directly
+    [Host, _Port] = binary:split(Endpoint, <<":">>),
+    {ok, DirectClient} = greptimedb_rs:start_client(
+        ConnOpts1#{proxy => Proxy#{no_proxy => [Host]}}
+    ),
+    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(DirectClient, <<"SELECT 1">>)),
+    receive
+        {proxied, _, _} = Proxied -> ct:fail({unexpected, Proxied})
+    after 0 -> ok
+    end,
+    ok = greptimedb_rs:stop_client(DirectClient),
+
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:start_client(ConnOpts1#{proxy => #{url => <<"ftp://127.0.0.1:21">>}})
+    ).
+
+%% The handshake runs through the tunnel against the endpoint host, so the server certificate
+%% verifies as on a direct connection
+t_proxy_tls(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    #{endpoints := [Endpoint]} = ConnOpts,
+    ProxyPort = start_proxy(http, undefined),
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts#{
+        pool_size => 1,
+        health_check_interval => infinity,
+        verify => verify_peer,
+        proxy => #{url => <<"http://127.0.0.1:", (integer_to_binary(ProxyPort))/binary>>}
+    }),
+    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+    receive
+        {proxied, http, Target} -> ?assertEqual(Endpoint, Target)
+    after 1000 -> ct:fail(not_proxied)
+    end,
+    ok = greptimedb_rs:stop_client(Client).
+
+t_proxy_socks5(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    #{endpoints := [Endpoint]} = ConnOpts,
+    ProxyPort = start_proxy(socks5, undefined),
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts#{
+        pool_size => 1,
+        health_check_interval => infinity,
+        proxy => #{url => <<"socks5://127.0.0.1:", (integer_to_binary(ProxyPort))/binary>>}
+    }),
+    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+    receive
+        {proxied, socks5, Target} -> ?assertEqual(Endpoint, Target)
+    after 1000 -> ct:fail(not_proxied)
+    end,
+    ok = greptimedb_rs:stop_client(Client).
+
+t_channel_options(Config) ->
+    Table = ?table(Config),
+    Ts = erlang:system_time(millisecond),
+    Rows = [
+        #{fields => #{<<"payload">> => binary:copy(<<"x">>, 4096)}, tags => #{}, timestamp => Ts}
+    ],
+    ChannelOpts = #{
+        connect_timeout => 3000,
+        request_timeout => 10000,
+        keepalive_interval => 10000,
+        keepalive_timeout => 5000,
+        keepalive_while_idle => true,
+        tcp_keepalive => 30000,
+        max_recv_message_size => 64 * 1024 * 1024,
+        initial_stream_window_size => 1024 * 1024,
+        initial_connection_window_size => 4 * 1024 * 1024
+    },
+    {ok, Client} = greptimedb_rs:start_client(maps:merge(?conn_opts(Config), ChannelOpts)),
+    ?assertMatch({ok, 1}, greptimedb_rs:insert(Client, Table, Rows)),
+    ok = greptimedb_rs:stop_client(Client),
+
+    %% The limits are passed down to the channel
+    {ok, SmallClient} = greptimedb_rs:start_client(
+        (?conn_opts(Config))#{max_send_message_size => 1024}
+    ),
+    ?assertMatch({error, _}, greptimedb_rs:insert(SmallClient, Table, Rows)),
+    ok = greptimedb_rs:stop_client(SmallClient),
+
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:start_client((?conn_opts(Config))#{connect_timeout => -1})
+    ).
+
+t_compression(Config) ->
+    Table = ?table(Config),
+    Ts = erlang:system_time(millisecond),
+    Rows = [
+        #{fields => #{<<"msg">> => binary:copy(<<"abc">>, 1000)}, tags => #{}, timestamp => Ts + I}
+     || I <- lists:seq(1, 10)
+    ],
+    lists:foreach(
+        fun(Compression) ->
+            {ok, Client} = greptimedb_rs:start_client(
+                (?conn_opts(Config))#{compression => Compression, pool_size => 1}
+            ),
+            ?assertMatch({ok, 10}, greptimedb_rs:insert(Client, Table, Rows)),
+            ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+            {ok, [#{compression := Compression} = Stats]} = greptimedb_rs:stats(Client),
+            ?assertNot(maps:is_key(compression_ratio, Stats)),
+            ok = greptimedb_rs:stop_client(Client)
+        end,
+        [gzip, zstd]
+    ),
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:start_client((?conn_opts(Config))#{compression => brotli})
+    ).
+
+t_stats(Config) ->
+    {ok, Client} = greptimedb_rs:start_client((?conn_opts(Config))#{pool_size => 1}),
+    Table = ?table(Config),
+    Ts = erlang:system_time(millisecond),
+    Rows = [
+        #{fields => #{<<"value">> => I}, tags => #{<<"host">> => <<"h1">>}, timestamp => Ts + I}
+     || I <- lists:seq(1, 5)
+    ],
+    ?assertMatch({ok, 5}, greptimedb_rs:insert(Client, Table, Rows)),
+    ?assertMatch({ok, _}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+    ?assertMatch({error, _}, greptimedb_rs:query(Client, <<"SELECT * FROM no_such_table">>)),
+    {ok, StreamClient} = greptimedb_rs:stream_start(Client, Table, hd(Rows)),
+    ok = greptimedb_rs:stream_write(StreamClient, Rows),
+
+    {ok, [Stats]} = greptimedb_rs:stats(Client),
+    ?assertMatch(
+        #{
+            inserts := 1,
+            queries := 2,
+            rows_written := 5,
+            in_flight := 0,
+            streams_open := 1,
+            errors := #{server := 1, conversion := 0},
+            insert_latency_us := #{count := 1},
+            query_latency_us := #{count := 2}
+        },
+        Stats
+    ),
+    #{insert_latency_us := #{buckets := Buckets}, bytes_sent := BytesSent} = Stats,
+    ?assert(BytesSent > 0),
+    ?assertMatch({infinity, 1}, lists:last(Buckets)),
+    ?assertMatch(
+        #{Table := #{writes := 1, rows_written := 5, write_latency_us := #{count := 1}}},
+        maps:get(streams, Stats)
+    ),
+
+    ok = greptimedb_rs:stream_close(StreamClient),
+    {ok, [#{streams_open := 0, streams := Streams}]} = greptimedb_rs:stats(Client),
+    ?assertEqual(#{}, Streams),
+    ok = greptimedb_rs:stop_client(Client).
+
+t_trace_context(Config) ->
+    %% Spans are batched, nothing needs to listen there for the calls to succeed
+    {ok, Client} = greptimedb_rs:start_client(
+        (?conn_opts(Config))#{otlp_endpoint => <<"http://127.0.0.1:4317">>}
+    ),
+    Table = ?table(Config),
+    Trace = #{
+        traceparent => <<"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01">>,
+        tracestate => <<"congo=t61rcWkgMzE">>
+    },
+    Rows = [
+        #{
+            fields => #{<<"value">> => I},
+            tags => #{<<"host">> => <<"h1">>},
+            timestamp => erlang:system_time(millisecond) + I
+        }
+     || I <- lists:seq(1, 3)
+    ],
+    ?assertMatch({ok, 3}, greptimedb_rs:insert(Client, Table, Rows, Trace)),
+    Sql = iolist_to_binary(io_lib:format("SELECT count(*) FROM ~s", [Table])),
+    ?assertMatch({ok, [[3]]}, greptimedb_rs:query(Client, Sql, Trace)),
+    %% Sent as an insert, the stream can't carry the trace context
+    {ok, StreamClient} = greptimedb_rs:stream_start(Client, Table, hd(Rows)),
+    Later = [R#{timestamp => T + 10} || R = #{timestamp := T} <- Rows],
+    ok = greptimedb_rs:stream_write(StreamClient, Later, Trace),
+    ok = greptimedb_rs:stream_close(StreamClient),
+    ?assertMatch({ok, [[6]]}, greptimedb_rs:query(Client, Sql, Trace)),
+
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:insert(Client, Table, Rows, #{traceparent => <<"00-abc-01">>})
+    ),
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:query(Client, Sql, #{tracestate => <<"congo=t61rcWkgMzE">>})
+    ),
+    ok = greptimedb_rs:stop_client(Client).
+
 t_connect_tls_without_client_certfiles(_Config) ->
     Host = get_host_addr("GREPTIMEDB_TLS_ADDR"),
     Dir = code:lib_dir(greptimedb_rs),
@@ -262,6 +558,68 @@ t_connect_tls_invalid_verify_returns_error(_Config) ->
             ct:fail(expected_invalid_verify_error)
     end.
 
+t_connect_tls_pem(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    Pems = maps:map(
+        fun(_Key, Path) ->
+            {ok, Pem} = file:read_file(Path),
+            Pem
+        end,
+        maps:with([ca_cert, client_cert, client_key], ConnOpts)
+    ),
+    TmpDir = os:getenv("TMPDIR", "/tmp"),
+    Before = filelib:wildcard("greptimedb_rs_tls_*", TmpDir),
+    {ok, Client} = greptimedb_rs:start_client(maps:merge(ConnOpts, Pems)),
+    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+    %% The PEM content, key included, is never written to disk
+    ?assertEqual(Before, filelib:wildcard("greptimedb_rs_tls_*", TmpDir)),
+    ok = greptimedb_rs:stop_client(Client).
+
+t_connect_tls_config_errors(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    AssertError = fun(Field, Opts) ->
+        {error, Reason} = greptimedb_rs:start_client(Opts),
+        Expected = iolist_to_binary(["tls_config,", atom_to_list(Field)]),
+        ?assertNotEqual(nomatch, binary:match(reason_to_binary(Reason), Expected), Reason)
+    end,
+    AssertError(ca_cert, ConnOpts#{ca_cert => <<"/nonexistent/ca.crt">>}),
+    AssertError(ca_cert, ConnOpts#{ca_cert => <<"-----BEGIN CERTIFICATE-----\ngarbage">>}),
+    AssertError(ca_cert, ConnOpts#{ca_cert => 42}),
+    %% A key is not a certificate
+    AssertError(client_cert, ConnOpts#{client_cert => maps:get(client_key, ConnOpts)}),
+    AssertError(client_key, maps:without([client_key], ConnOpts)),
+    AssertError(client_cert, maps:without([client_cert], ConnOpts)),
+    AssertError(verify, ConnOpts#{verify => bad_verify}),
+
+    %% Reloading validates the same way, and keeps the working channels on error
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts),
+    ?assertMatch(
+        {error, {tls_config, ca_cert, _}},
+        greptimedb_rs:reload_tls(Client, #{ca_cert => <<"/nonexistent/ca.crt">>})
+    ),
+    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+    ok = greptimedb_rs:stop_client(Client).
+
+t_reload_tls(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts#{pool_size => 2}),
+    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+
+    %% Read the same files again
+    ok = greptimedb_rs:reload_tls(Client, #{}),
+    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+
+    %% Switch to in-memory certificates
+    {ok, CaPem} = file:read_file(maps:get(ca_cert, ConnOpts)),
+    ok = greptimedb_rs:reload_tls(Client, #{ca_cert => CaPem}),
+    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+    ok = greptimedb_rs:stop_client(Client),
+
+    %% Not a TLS connection
+    {ok, TcpClient} = greptimedb_rs:start_client(maps:without([tls], ConnOpts)),
+    ?assertMatch({error, _}, greptimedb_rs:reload_tls(TcpClient, #{})),
+    ok = greptimedb_rs:stop_client(TcpClient).
+
 t_metadata_queries(Config) ->
     {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
     Table = ?table(Config),
@@ -328,6 +686,92 @@ t_connect_with_auth(_Config) ->
     ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
     ok = greptimedb_rs:stop_client(Client).
 
+t_update_auth(_Config) ->
+    Host = get_host_addr("GREPTIMEDB_AUTH_ADDR"),
+    ConnOpts = #{
+        endpoints => [<<Host/binary, ":4001">>],
+        dbname => <<"public">>,
+        username => <<"greptime_user">>,
+        password => <<"wrong_pwd">>,
+        pool_size => 2
+    },
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts),
+    ?assertMatch({error, _}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+
+    %% Every connection of the pool picks up the new credentials
+    ok = greptimedb_rs:update_auth(
+        Client, #{username => <<"greptime_user">>, password => fun() -> <<"greptime_pwd">> end}
+    ),
+    lists:foreach(
+        fun(_) -> ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)) end,
+        lists:seq(1, 4)
+    ),
+
+    ?assertMatch({error, _}, greptimedb_rs:update_auth(Client, #{})),
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:update_auth(Client, #{token => <<"t">>, username => <<"u">>})
+    ),
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:update_auth(Client, #{token => <<"t">>, password => <<"p">>})
+    ),
+    ?assertMatch({error, _}, greptimedb_rs:update_auth(Client, #{username => <<"u">>})),
+    ?assertMatch({error, _}, greptimedb_rs:update_auth(Client, #{password => <<"p">>})),
+    ok = greptimedb_rs:stop_client(Client),
+
+    %% Incomplete credentials are rejected at start too
+    ?assertMatch(
+        {error, _}, greptimedb_rs:start_client(maps:remove(password, ConnOpts))
+    ).
+
+t_log_handler(_Config) ->
+    ?assertMatch({error, _}, greptimedb_rs:set_log_level(verbose)),
+    ?assertMatch({error, _}, greptimedb_rs:set_log_handler(not_a_pid)),
+    ?assertMatch({error, _}, greptimedb_rs:set_log_handler(self(), #{max_per_second => 0})),
+
+    ok = greptimedb_rs:set_log_level(trace),
+    ok = greptimedb_rs:set_log_handler(self(), #{max_per_second => 1}),
+    %% Nothing listens there, every query logs its connection attempts
+    {ok, Client} = greptimedb_rs:start_client(#{
+        endpoints => [<<"127.0.0.1:1">>],
+        dbname => <<"public">>,
+        health_check_interval => infinity,
+        pool_size => 1
+    }),
+    lists:foreach(
+        fun(_) ->
+            greptimedb_rs:query(Client, <<"SELECT 1">>),
+            timer:sleep(200)
+        end,
+        lists:seq(1, 15)
+    ),
+    ok = greptimedb_rs:stop_client(Client),
+    ok = greptimedb_rs:set_log_handler(undefined),
+    ok = greptimedb_rs:set_log_level(warning),
+
+    Logs = collect_logs([]),
+    ?assertMatch([{_, _, #{target := _}} | _], Logs),
+    %% About 3 seconds at one record per second, the rest is dropped and reported
+    ?assert(length(Logs) =< 8),
+    ?assert(
+        lists:any(
+            fun
+                ({warning, Message, _}) -> binary:match(Message, <<"dropped">>) =/= nomatch;
+                (_) -> false
+            end,
+            Logs
+        )
+    ).
+
+collect_logs(Acc) ->
+    receive
+        {greptimedb_rs_log, Level, Message, Meta} ->
+            collect_logs([{Level, Message, Meta} | Acc])
+    after 500 ->
+        lists:reverse(Acc)
+    end.
+
 t_insert_sync(Config) ->
     {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
     Table = ?table(Config),
@@ -430,6 +874,68 @@ t_insert_sync_ttl_hint(Config) ->
 
     ok = greptimedb_rs:stop_client(Client).
 
+t_insert_sync_default_timestamp(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    ConnOpts1 = ConnOpts#{default_timestamp => now},
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts1),
+    Table = ?table(Config),
+
+    %% Drop table if exists
+    DropTableSql = iolist_to_binary(io_lib:format("DROP TABLE IF EXISTS ~s", [Table])),
+    greptimedb_rs:query(Client, DropTableSql),
+
+    %% No `timestamp` nor `ts` in the rows
+    Rows = [
+        #{
+            fields => #{<<"temperature">> => 25.5},
+            tags => #{<<"sensor_id">> => 1}
+        },
+        #{
+            fields => #{<<"temperature">> => 26.5},
+            tags => #{<<"sensor_id">> => 2}
+        }
+    ],
+    Before = erlang:system_time(millisecond),
+    ?assertMatch({ok, 2}, greptimedb_rs:insert(Client, Table, Rows)),
+    timer:sleep(1000),
+
+    Sql = iolist_to_binary(
+        io_lib:format("SELECT count(*) FROM ~s WHERE ts >= ~p", [Table, Before])
+    ),
+    ?assertMatch({ok, [[2]]}, greptimedb_rs:query(Client, Sql)),
+
+    %% `error` rejects rows without timestamp
+    ok = greptimedb_rs:stop_client(Client),
+    {ok, Client1} = greptimedb_rs:start_client(ConnOpts#{default_timestamp => error}),
+    ?assertMatch({error, {nif_error, _}}, greptimedb_rs:insert(Client1, Table, Rows)),
+
+    ok = greptimedb_rs:stop_client(Client1).
+
+t_insert_sync_default_timestamp_column(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    ConnOpts1 = ConnOpts#{default_timestamp => {column, <<"event_ms">>}},
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts1),
+    Table = ?table(Config),
+
+    %% Drop table if exists
+    DropTableSql = iolist_to_binary(io_lib:format("DROP TABLE IF EXISTS ~s", [Table])),
+    greptimedb_rs:query(Client, DropTableSql),
+
+    Ts = 1700000000000,
+    Rows = [
+        #{
+            fields => #{<<"temperature">> => 25.5, <<"event_ms">> => Ts},
+            tags => #{<<"sensor_id">> => 1}
+        }
+    ],
+    ?assertMatch({ok, 1}, greptimedb_rs:insert(Client, Table, Rows)),
+    timer:sleep(1000),
+
+    Sql = iolist_to_binary(io_lib:format("SELECT ts FROM ~s", [Table])),
+    ?assertMatch({ok, [[Ts]]}, greptimedb_rs:query(Client, Sql)),
+
+    ok = greptimedb_rs:stop_client(Client).
+
 t_insert_sync_existing_table(Config) ->
     {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
     Table = ?table(Config),
@@ -550,6 +1056,276 @@ t_insert_sync_schema_conflict(Config) ->
 
     ok = greptimedb_rs:stop_client(Client).
 
+t_insert_sync_coercion(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    Table = ?table(Config),
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts),
+
+    %% Drop table if exists
+    DropTableSql = iolist_to_binary(io_lib:format("DROP TABLE IF EXISTS ~s", [Table])),
+    greptimedb_rs:query(Client, DropTableSql),
+
+    CreateTableSql = iolist_to_binary(
+        io_lib:format(
+            "CREATE TABLE ~s ("
+            "ts TIMESTAMP TIME INDEX, "
+            "val_float DOUBLE, "
+            "val_int INT32, "
+            "val_str STRING"
+            ") ENGINE=mito",
+            [Table]
+        )
+    ),
+    ?assertMatch({ok, _}, greptimedb_rs:query(Client, CreateTableSql)),
+
+    Ts = erlang:system_time(millisecond),
+    Row = fun(I, Fields) -> #{fields => Fields, timestamp => Ts + I} end,
+    Widen = [
+        Row(0, #{<<"val_float">> => 1, <<"val_int">> => 2.9}),
+        Row(1, #{<<"val_float">> => <<"1.5">>, <<"val_int">> => <<"42">>})
+    ],
+    Stringify = [Row(2, #{<<"val_str">> => 12}), Row(3, #{<<"val_str">> => abc})],
+    Invalid = [Row(4, #{<<"val_int">> => <<"not a number">>})],
+    OutOfRange = [Row(5, #{<<"val_int">> => 1.0e10}), Row(6, #{<<"val_int">> => <<"NaN">>})],
+
+    %% strict (default) rejects mismatched types
+    ?assertMatch({error, {nif_error, _}}, greptimedb_rs:insert(Client, Table, Widen)),
+    ok = greptimedb_rs:stop_client(Client),
+
+    {ok, Client1} = greptimedb_rs:start_client(ConnOpts#{coercion => widen}),
+    ?assertMatch({ok, 2}, greptimedb_rs:insert(Client1, Table, Widen)),
+    ?assertMatch({error, {nif_error, _}}, greptimedb_rs:insert(Client1, Table, Stringify)),
+    %% floats are truncated, but only within the range of the column
+    ?assertMatch(
+        {ok, 0, [{1, <<"val_int">>, _}, {2, <<"val_int">>, _}]},
+        greptimedb_rs:insert(Client1, Table, OutOfRange, #{partial_write => true})
+    ),
+    ok = greptimedb_rs:stop_client(Client1),
+
+    {ok, Client2} = greptimedb_rs:start_client(ConnOpts#{coercion => stringify}),
+    ?assertMatch({ok, 2}, greptimedb_rs:insert(Client2, Table, Stringify)),
+    ?assertMatch({error, {nif_error, _}}, greptimedb_rs:insert(Client2, Table, Invalid)),
+    ok = greptimedb_rs:stop_client(Client2),
+
+    {ok, Client3} = greptimedb_rs:start_client(ConnOpts#{coercion => null_on_error}),
+    ?assertMatch({ok, 1}, greptimedb_rs:insert(Client3, Table, Invalid)),
+
+    timer:sleep(1000),
+    Sql = iolist_to_binary(
+        io_lib:format("SELECT val_float, val_int, val_str FROM ~s ORDER BY ts", [Table])
+    ),
+    ?assertMatch(
+        {ok, [
+            [1.0, 2, nil],
+            [1.5, 42, nil],
+            [nil, nil, <<"12">>],
+            [nil, nil, <<"abc">>],
+            [nil, nil, nil]
+        ]},
+        greptimedb_rs:query(Client3, Sql)
+    ),
+
+    ok = greptimedb_rs:stop_client(Client3).
+
+t_insert_sync_partial_write(Config) ->
+    {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
+    Table = ?table(Config),
+
+    %% Drop table if exists
+    DropTableSql = iolist_to_binary(io_lib:format("DROP TABLE IF EXISTS ~s", [Table])),
+    greptimedb_rs:query(Client, DropTableSql),
+
+    CreateTableSql = iolist_to_binary(
+        io_lib:format(
+            "CREATE TABLE ~s ("
+            "ts TIMESTAMP TIME INDEX, "
+            "val_int8 INT8, "
+            "val_pk INT32, "
+            "PRIMARY KEY (val_pk)"
+            ") ENGINE=mito",
+            [Table]
+        )
+    ),
+    ?assertMatch({ok, _}, greptimedb_rs:query(Client, CreateTableSql)),
+
+    Ts = erlang:system_time(millisecond),
+    Rows = [
+        #{fields => #{<<"val_int8">> => 1}, tags => #{<<"val_pk">> => 1}, timestamp => Ts},
+        %% Overflow
+        #{fields => #{<<"val_int8">> => 1000}, tags => #{<<"val_pk">> => 2}, timestamp => Ts},
+        #{fields => #{<<"val_int8">> => 3}, tags => #{<<"val_pk">> => 3}, timestamp => Ts},
+        %% Type mismatch on the primary key
+        #{fields => #{<<"val_int8">> => 4}, tags => #{<<"val_pk">> => <<"bad">>}, timestamp => Ts}
+    ],
+
+    %% Without the option the whole batch fails
+    ?assertMatch({error, {nif_error, _}}, greptimedb_rs:insert(Client, Table, Rows)),
+
+    ?assertMatch(
+        {ok, 2, [{2, <<"val_int8">>, _}, {4, <<"val_pk">>, _}]},
+        greptimedb_rs:insert(Client, Table, Rows, #{partial_write => true})
+    ),
+    ?assertMatch(
+        {ok, 0, [{1, <<"val_int8">>, _}]},
+        greptimedb_rs:insert(Client, Table, [lists:nth(2, Rows)], #{partial_write => true})
+    ),
+
+    timer:sleep(1000),
+    Sql = iolist_to_binary(io_lib:format("SELECT val_pk FROM ~s ORDER BY val_pk", [Table])),
+    ?assertMatch({ok, [[1], [3]]}, greptimedb_rs:query(Client, Sql)),
+
+    ok = greptimedb_rs:stop_client(Client).
+
+t_insert_sync_infer_schema_from_all_rows(Config) ->
+    {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
+    Table = ?table(Config),
+
+    %% Drop table if exists
+    DropTableSql = iolist_to_binary(io_lib:format("DROP TABLE IF EXISTS ~s", [Table])),
+    greptimedb_rs:query(Client, DropTableSql),
+
+    Ts = erlang:system_time(millisecond),
+    Rows = [
+        #{
+            fields => #{<<"value">> => 1},
+            tags => #{<<"host">> => <<"a">>},
+            timestamp => Ts
+        },
+        %% `value' widens to Float64, `region' and `status' first show up here
+        #{
+            fields => #{<<"value">> => 1.5, <<"status">> => <<"up">>},
+            tags => #{<<"host">> => <<"b">>, <<"region">> => <<"eu">>},
+            timestamp => Ts + 1
+        }
+    ],
+    ?assertMatch({ok, 2}, greptimedb_rs:insert(Client, Table, Rows)),
+    timer:sleep(1000),
+
+    Sql = iolist_to_binary(
+        io_lib:format("SELECT host, region, status, \"value\" FROM ~s ORDER BY ts", [Table])
+    ),
+    ?assertMatch(
+        {ok, [[<<"a">>, nil, nil, 1.0], [<<"b">>, <<"eu">>, <<"up">>, 1.5]]},
+        greptimedb_rs:query(Client, Sql)
+    ),
+
+    %% Any other mix widens to String, even on a strict connection
+    MixedTable = <<Table/binary, "_mixed">>,
+    MixedRows = [
+        #{fields => #{<<"value">> => 1}, timestamp => Ts},
+        #{fields => #{<<"value">> => true}, timestamp => Ts + 1}
+    ],
+    ?assertMatch({ok, 2}, greptimedb_rs:insert(Client, MixedTable, MixedRows)),
+    timer:sleep(1000),
+    MixedSql = iolist_to_binary(io_lib:format("SELECT \"value\" FROM ~s ORDER BY ts", [MixedTable])),
+    ?assertMatch({ok, [[<<"1">>], [<<"true">>]]}, greptimedb_rs:query(Client, MixedSql)),
+
+    %% A name can't be both a tag and a field
+    ConflictTable = <<Table/binary, "_conflict">>,
+    ConflictRows = [
+        #{fields => #{<<"host">> => 1}, tags => #{<<"host">> => <<"a">>}, timestamp => Ts}
+    ],
+    ?assertMatch(
+        {error, {nif_error, {schema_conflict, [{<<"host">>, _}]}}},
+        greptimedb_rs:insert(Client, ConflictTable, ConflictRows)
+    ),
+
+    ok = greptimedb_rs:stop_client(Client).
+
+t_insert_sync_schema_hints(Config) ->
+    ConnOpts = ?conn_opts(Config),
+    ConnOpts1 = ConnOpts#{schema_hints => #{<<"level">> => uint8}},
+    {ok, Client} = greptimedb_rs:start_client(ConnOpts1),
+    Table = ?table(Config),
+
+    %% Drop table if exists
+    DropTableSql = iolist_to_binary(io_lib:format("DROP TABLE IF EXISTS ~s", [Table])),
+    greptimedb_rs:query(Client, DropTableSql),
+
+    Ts = erlang:system_time(microsecond),
+    Rows = [
+        #{
+            fields => #{
+                <<"level">> => 3,
+                <<"payload">> => <<1, 2, 3>>,
+                <<"device">> => <<"dev-1">>
+            },
+            timestamp => Ts
+        }
+    ],
+    Hints = #{
+        <<"payload">> => binary,
+        <<"device">> => {string, tag},
+        <<"ts">> => timestamp_microsecond
+    },
+    %% The time index must stay a timestamp
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:insert(Client, Table, Rows, #{schema_hints => #{<<"ts">> => string}})
+    ),
+    ?assertMatch({ok, 1}, greptimedb_rs:insert(Client, Table, Rows, #{schema_hints => Hints})),
+    timer:sleep(1000),
+
+    DescSql = iolist_to_binary(io_lib:format("DESC TABLE ~s", [Table])),
+    {ok, Columns} = greptimedb_rs:query(Client, DescSql),
+    ColumnTypes = maps:from_list(
+        [{Name, {Type, Semantic}} || [Name, Type, _, _, _, Semantic | _] <- Columns]
+    ),
+    ?assertMatch(
+        #{
+            <<"level">> := {<<"UInt8">>, <<"FIELD">>},
+            <<"payload">> := {<<"Binary">>, <<"FIELD">>},
+            <<"device">> := {<<"String">>, <<"TAG">>},
+            <<"ts">> := {<<"TimestampMicrosecond">>, <<"TIMESTAMP">>}
+        },
+        ColumnTypes
+    ),
+
+    ok = greptimedb_rs:stop_client(Client).
+
+t_insert_sync_retry(Config) ->
+    Retry = #{max_attempts => 3, initial_backoff => 10, max_backoff => 50},
+    {ok, Client} = greptimedb_rs:start_client((?conn_opts(Config))#{retry => Retry}),
+    Table = ?table(Config),
+    Ts = erlang:system_time(millisecond),
+    Rows = [#{fields => #{<<"val">> => 1}, tags => #{<<"host">> => <<"a">>}, timestamp => Ts}],
+
+    %% Succeeds at once, the attempts are still reported
+    ?assertMatch({ok, 1, #{attempts := 1}}, greptimedb_rs:insert(Client, Table, Rows)),
+    ?assertMatch(
+        {ok, 1, [], #{attempts := 1}},
+        greptimedb_rs:insert(Client, Table, Rows, #{partial_write => true})
+    ),
+    ok = greptimedb_rs:stop_client(Client),
+
+    %% Nothing listens on this one, every attempt fails with `Unavailable'
+    {ok, BadClient} = greptimedb_rs:start_client(
+        (?conn_opts(Config))#{
+            endpoints => [<<"127.0.0.1:1">>],
+            health_check_interval => infinity,
+            retry => Retry
+        }
+    ),
+    ?assertMatch(
+        {error, {_, #{attempts := 3}}},
+        greptimedb_rs:insert(BadClient, Table, Rows)
+    ),
+    ok = greptimedb_rs:stop_client(BadClient),
+
+    %% Invalid policies are rejected
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:start_client((?conn_opts(Config))#{retry => #{retry_on => [bogus]}})
+    ),
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:start_client((?conn_opts(Config))#{retry => #{max_attempts => 0}})
+    ),
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:start_client((?conn_opts(Config))#{retry => #{deadline => 0}})
+    ).
+
 t_insert_async(Config) ->
     {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
     Table = ?table(Config),
@@ -952,6 +1728,310 @@ t_stream_write_async(Config) ->
 
     ok = greptimedb_rs:stop_client(Client).
 
+t_buffered_write(Config) ->
+    {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
+    Table = ?table(Config),
+    Ts = erlang:system_time(millisecond),
+    Row = fun(I) ->
+        #{fields => #{<<"value">> => I}, tags => #{<<"host">> => <<"h1">>}, timestamp => Ts + I}
+    end,
+    Opts = #{max_rows => 10, linger => 200, notify => self()},
+    {ok, Writer} = greptimedb_rs:buffer_start(Client, Opts),
+
+    %% Flushed on max_rows
+    lists:foreach(
+        fun(I) -> ok = greptimedb_rs:append(Writer, Table, [Row(I)]) end,
+        lists:seq(1, 10)
+    ),
+    receive
+        {greptimedb_rs_flush, Table, Result1} -> ?assertEqual({ok, 10}, Result1)
+    after 5000 ->
+        ct:fail(flush_on_max_rows_timeout)
+    end,
+
+    %% Flushed on linger
+    ok = greptimedb_rs:append(Writer, Table, [Row(11), Row(12)]),
+    receive
+        {greptimedb_rs_flush, Table, Result2} -> ?assertEqual({ok, 2}, Result2)
+    after 5000 ->
+        ct:fail(flush_on_linger_timeout)
+    end,
+
+    ok = greptimedb_rs:append(Writer, Table, [Row(13)]),
+    ok = greptimedb_rs:buffer_close(Writer),
+    receive
+        {greptimedb_rs_flush, Table, Result3} -> ?assertEqual({ok, 1}, Result3)
+    after 0 ->
+        ct:fail(flush_on_close_missing)
+    end,
+    ?assertMatch({error, _}, greptimedb_rs:append(Writer, Table, [Row(14)])),
+
+    Sql = iolist_to_binary(io_lib:format("SELECT count(*) FROM ~s", [Table])),
+    ?assertMatch({ok, [[13]]}, greptimedb_rs:query(Client, Sql)),
+    ?assertMatch({error, _}, greptimedb_rs:buffer_start(Client, #{max_rows => 0})),
+    ok = greptimedb_rs:stop_client(Client).
+
+t_spool(Config) ->
+    Table = ?table(Config),
+    Spool = #{dir => filename:join(?config(priv_dir, Config), Table), replay_interval => 100},
+    Ts = erlang:system_time(millisecond),
+    Rows = [
+        #{fields => #{<<"value">> => I}, tags => #{<<"host">> => <<"h1">>}, timestamp => Ts + I}
+     || I <- lists:seq(1, 4)
+    ],
+
+    %% Nothing listens there, the rows are kept on disk
+    {ok, DownClient} = greptimedb_rs:start_client(
+        (?conn_opts(Config))#{
+            endpoints => [<<"127.0.0.1:1">>],
+            spool => Spool,
+            pool_name => spool_down,
+            pool_size => 1
+        }
+    ),
+    ?assertEqual({ok, spooled}, greptimedb_rs:insert(DownClient, Table, lists:sublist(Rows, 2))),
+    ?assertEqual({ok, spooled}, greptimedb_rs:insert(DownClient, Table, lists:nthtail(2, Rows))),
+    ?assertMatch(
+        {ok, [#{records := 2, spooled := 2, replayed := 0}]},
+        greptimedb_rs:spool_status(DownClient)
+    ),
+    ok = greptimedb_rs:stop_client(DownClient),
+
+    %% Replayed by the next connection spooling to the same directory
+    {ok, Client} = greptimedb_rs:start_client(
+        (?conn_opts(Config))#{spool => Spool, pool_size => 1}
+    ),
+    ok = wait_until(
+        fun() ->
+            {ok, [Status]} = greptimedb_rs:spool_status(Client),
+            maps:get(records, Status) =:= 0
+        end,
+        50
+    ),
+    Sql = iolist_to_binary(io_lib:format("SELECT count(*) FROM ~s", [Table])),
+    ?assertMatch({ok, [[4]]}, greptimedb_rs:query(Client, Sql)),
+    %% There is nowhere to spool an insert into another database
+    ?assertMatch(
+        {error, {nif_error, _}},
+        greptimedb_rs:insert(Client, Table, Rows, #{db => <<"public">>})
+    ),
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:start_client((?conn_opts(Config))#{spool => #{eviction => lru}})
+    ),
+    ok = greptimedb_rs:stop_client(Client).
+
+t_in_flight_limits(Config) ->
+    {ok, Client} = greptimedb_rs:start_client(
+        (?conn_opts(Config))#{max_in_flight => #{bytes => 1}, pool_size => 1}
+    ),
+    Table = ?table(Config),
+    Ts = erlang:system_time(millisecond),
+    Row = fun(I) ->
+        #{fields => #{<<"value">> => I}, tags => #{<<"host">> => <<"h1">>}, timestamp => Ts + I}
+    end,
+    {ok, Writer} = greptimedb_rs:buffer_start(Client, #{linger => 60000, notify => self()}),
+
+    %% Alone in flight, the rows go through despite the limit and hold it until flushed
+    ok = greptimedb_rs:append(Writer, Table, [Row(1)]),
+    ?assertEqual({error, overloaded}, greptimedb_rs:append(Writer, Table, [Row(2)])),
+    ?assertEqual({error, overloaded}, greptimedb_rs:insert(Client, Table, [Row(3)])),
+    {ok, [#{in_flight_bytes := Bytes, overloaded := 2}]} = greptimedb_rs:stats(Client),
+    ?assert(Bytes > 1),
+
+    ok = greptimedb_rs:buffer_flush(Writer),
+    receive
+        {greptimedb_rs_flush, Table, Result} -> ?assertEqual({ok, 1}, Result)
+    after 5000 ->
+        ct:fail(flush_timeout)
+    end,
+    ok = wait_until(
+        fun() ->
+            {ok, [#{in_flight_bytes := InFlight}]} = greptimedb_rs:stats(Client),
+            InFlight =:= 0
+        end,
+        50
+    ),
+    ?assertEqual({ok, 1}, greptimedb_rs:insert(Client, Table, [Row(3)])),
+    ok = greptimedb_rs:buffer_close(Writer),
+    ok = greptimedb_rs:stop_client(Client),
+
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:start_client((?conn_opts(Config))#{max_in_flight => #{requests => 0}})
+    ).
+
+t_idempotent_insert(Config) ->
+    {ok, Client} = greptimedb_rs:start_client((?conn_opts(Config))#{dedup => #{capacity => 2}}),
+    Table = ?table(Config),
+    Ts = erlang:system_time(millisecond),
+    Rows = [
+        #{fields => #{<<"value">> => I}, tags => #{<<"host">> => <<"h1">>}, timestamp => Ts + I}
+     || I <- lists:seq(1, 3)
+    ],
+    Opts = #{idempotency_key => <<"batch-1">>},
+
+    ?assertEqual({ok, 3, #{deduplicated => false}}, greptimedb_rs:insert(Client, Table, Rows, Opts)),
+    ?assertEqual({ok, 3, #{deduplicated => true}}, greptimedb_rs:insert(Client, Table, Rows, Opts)),
+    ?assertMatch(
+        {ok, 3, [], #{deduplicated := true}},
+        greptimedb_rs:insert(Client, Table, Rows, Opts#{partial_write => true})
+    ),
+    {ok, [#{deduplicated := Deduplicated} | _]} = greptimedb_rs:stats(Client),
+    ?assertEqual(2, Deduplicated),
+
+    %% Keys are per table
+    Other = <<Table/binary, "_other">>,
+    ?assertEqual({ok, 3, #{deduplicated => false}}, greptimedb_rs:insert(Client, Other, Rows, Opts)),
+
+    %% Over the capacity, the least recently used key is forgotten
+    ?assertEqual({ok, 3, #{deduplicated => true}}, greptimedb_rs:insert(Client, Table, Rows, Opts)),
+    NewOpts = #{idempotency_key => <<"batch-3">>},
+    ?assertEqual(
+        {ok, 3, #{deduplicated => false}}, greptimedb_rs:insert(Client, Table, Rows, NewOpts)
+    ),
+    ?assertEqual({ok, 3, #{deduplicated => true}}, greptimedb_rs:insert(Client, Table, Rows, Opts)),
+    ?assertEqual({ok, 3, #{deduplicated => false}}, greptimedb_rs:insert(Client, Other, Rows, Opts)),
+    ?assertMatch(
+        {error, {nif_error, _}},
+        greptimedb_rs:insert(Client, Table, Rows, #{idempotency_key => 1})
+    ),
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:start_client((?conn_opts(Config))#{dedup => #{window => 0}})
+    ),
+    ok = greptimedb_rs:stop_client(Client),
+
+    %% A skipped resend is not sent, it reports no attempts
+    {ok, RetryClient} = greptimedb_rs:start_client((?conn_opts(Config))#{retry => #{}}),
+    RetryOpts = #{idempotency_key => <<"batch-2">>},
+    ?assertEqual(
+        {ok, 3, #{attempts => 1, deduplicated => false}},
+        greptimedb_rs:insert(RetryClient, Table, Rows, RetryOpts)
+    ),
+    ?assertEqual(
+        {ok, 3, #{deduplicated => true}},
+        greptimedb_rs:insert(RetryClient, Table, Rows, RetryOpts)
+    ),
+    ok = greptimedb_rs:stop_client(RetryClient).
+
+t_delete(Config) ->
+    {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
+    Table = ?table(Config),
+    Ts = erlang:system_time(millisecond),
+    Row = fun(Host, I) ->
+        #{fields => #{<<"value">> => I}, tags => #{<<"host">> => Host}, timestamp => Ts + I}
+    end,
+    Rows = [Row(<<"h1">>, I) || I <- lists:seq(1, 3)] ++ [Row(<<"h2">>, I) || I <- lists:seq(1, 3)],
+    {ok, 6} = greptimedb_rs:insert(Client, Table, Rows),
+
+    %% Identified by tags and timestamp, whatever the fields say
+    Keys = [#{tags => #{<<"host">> => <<"h1">>}, timestamp => Ts + I} || I <- lists:seq(1, 3)],
+    ?assertEqual({ok, 3}, greptimedb_rs:delete(Client, Table, Keys)),
+    Sql = iolist_to_binary(io_lib:format("SELECT host, count(*) FROM ~s GROUP BY host", [Table])),
+    ?assertMatch({ok, [[<<"h2">>, 3]]}, greptimedb_rs:query(Client, Sql)),
+
+    ?assertEqual({ok, 0}, greptimedb_rs:delete(Client, Table, [])),
+    ?assertMatch({error, _}, greptimedb_rs:delete(Client, <<Table/binary, "_missing">>, Keys)),
+    ok = greptimedb_rs:stop_client(Client),
+
+    %% The attempts are reported with a retry policy, no timestamp is made up for a row
+    {ok, RetryClient} = greptimedb_rs:start_client(
+        (?conn_opts(Config))#{pool_size => 1, retry => #{}, default_timestamp => now}
+    ),
+    H2Keys = [#{tags => #{<<"host">> => <<"h2">>}, timestamp => Ts + I} || I <- lists:seq(1, 3)],
+    ?assertEqual({ok, 3, #{attempts => 1}}, greptimedb_rs:delete(RetryClient, Table, H2Keys)),
+    ?assertMatch(
+        {error, _},
+        greptimedb_rs:delete(RetryClient, Table, [#{tags => #{<<"host">> => <<"h2">>}}])
+    ),
+    ?assertMatch(
+        {ok, [#{deletes := 2, delete_latency_us := #{count := 1}}]},
+        greptimedb_rs:stats(RetryClient)
+    ),
+    ok = greptimedb_rs:stop_client(RetryClient).
+
+t_introspection(Config) ->
+    {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
+    Table = ?table(Config),
+    ?assertEqual({ok, false}, greptimedb_rs:table_exists(Client, Table)),
+    Row = #{
+        fields => #{<<"value">> => 1.5},
+        tags => #{<<"host">> => <<"h1">>},
+        timestamp => erlang:system_time(millisecond)
+    },
+    {ok, 1} = greptimedb_rs:insert(Client, Table, [Row]),
+
+    ?assertEqual({ok, true}, greptimedb_rs:table_exists(Client, Table)),
+    {ok, Dbs} = greptimedb_rs:list_databases(Client),
+    ?assert(lists:member(<<"public">>, Dbs)),
+    {ok, Tables} = greptimedb_rs:list_tables(Client, <<"public">>),
+    ?assert(lists:member(Table, Tables)),
+
+    {ok, Columns} = greptimedb_rs:describe_table(Client, Table),
+    ByName = maps:from_list([{Name, C} || #{name := Name} = C <- Columns]),
+    ?assertMatch(
+        #{semantic_type := tag, data_type := <<"String">>, indexes := [primary_key]},
+        maps:get(<<"host">>, ByName)
+    ),
+    ?assertMatch(
+        #{semantic_type := field, data_type := <<"Float64">>, nullable := true},
+        maps:get(<<"value">>, ByName)
+    ),
+    ?assertMatch(
+        #{semantic_type := timestamp, nullable := false, indexes := [time_index]},
+        maps:get(<<"ts">>, ByName)
+    ),
+    ?assertMatch({error, _}, greptimedb_rs:describe_table(Client, <<Table/binary, "_missing">>)),
+    %% The name is quoted, not run as SQL
+    Injected = <<Table/binary, "\"; DROP TABLE ", Table/binary, "; --">>,
+    ?assertMatch({error, _}, greptimedb_rs:describe_table(Client, Injected)),
+    ?assertMatch({error, _}, greptimedb_rs:delete(Client, Injected, [#{timestamp => 1}])),
+    ?assertEqual({ok, true}, greptimedb_rs:table_exists(Client, Table)),
+    ok = greptimedb_rs:stop_client(Client).
+
+t_per_call_db(Config) ->
+    {ok, Client} = greptimedb_rs:start_client(?conn_opts(Config)),
+    Table = ?table(Config),
+    Db = <<Table/binary, "_db">>,
+    CreateDb = iolist_to_binary(io_lib:format("CREATE DATABASE IF NOT EXISTS ~s", [Db])),
+    {ok, _} = greptimedb_rs:query(Client, CreateDb),
+    Opts = #{db => Db},
+    Ts = erlang:system_time(millisecond),
+    Row = fun(I) ->
+        #{fields => #{<<"value">> => I}, tags => #{<<"host">> => <<"h1">>}, timestamp => Ts + I}
+    end,
+
+    ?assertEqual({ok, 1}, greptimedb_rs:insert(Client, Table, [Row(1)], Opts)),
+    %% On the handle of the database made for the first call
+    ?assertEqual({ok, 1}, greptimedb_rs:insert(Client, Table, [Row(2)], Opts)),
+    Sql = iolist_to_binary(io_lib:format("SELECT count(*) FROM ~s", [Table])),
+    ?assertMatch({ok, [[2]]}, greptimedb_rs:query(Client, Sql, Opts)),
+    ?assertEqual({ok, false}, greptimedb_rs:table_exists(Client, Table)),
+
+    {ok, Stream} = greptimedb_rs:stream_start(Client, Table, Row(3), Opts),
+    ?assertMatch({stream_client, _, {Db, Table}}, Stream),
+    ok = greptimedb_rs:stream_write(Stream, [Row(3)]),
+    ok = greptimedb_rs:stream_close(Stream),
+    ok = wait_until(fun() -> greptimedb_rs:query(Client, Sql, Opts) =:= {ok, [[3]]} end, 50),
+
+    ?assertMatch({error, _}, greptimedb_rs:insert(Client, Table, [Row(4)], #{db => <<"no_such_db">>})),
+    ?assertMatch({error, {nif_error, _}}, greptimedb_rs:query(Client, Sql, #{db => 1})),
+    DropDb = iolist_to_binary(io_lib:format("DROP DATABASE ~s", [Db])),
+    {ok, _} = greptimedb_rs:query(Client, DropDb),
+    ok = greptimedb_rs:stop_client(Client).
+
+wait_until(_Fun, 0) ->
+    timeout;
+wait_until(Fun, Retries) ->
+    case Fun() of
+        true ->
+            ok;
+        false ->
+            timer:sleep(100),
+            wait_until(Fun, Retries - 1)
+    end.
+
 %% ================================================================================
 %% TLS Cipher Suite Test Cases
 %% ================================================================================
@@ -972,6 +2052,10 @@ t_cipher_tls13_aes128_gcm(Config) ->
     },
     {ok, Client} = greptimedb_rs:start_client(ConnOpts),
     ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
+    ?assertMatch(
+        {ok, [#{cipher_suite := <<"TLS_AES_128_GCM_SHA256">>}]},
+        greptimedb_rs:connection_status(Client)
+    ),
     ok = greptimedb_rs:stop_client(Client).
 
 %% TLS 1.2 cipher: ECDHE-RSA-AES256
@@ -1041,8 +2125,8 @@ t_cipher_all_invalid_returns_error(Config) ->
             ok = greptimedb_rs:stop_client(Client)
     end.
 
-%% Mix of valid + invalid cipher names: invalid ones are skipped, valid ones work
-t_cipher_partial_invalid_skips_bad(Config) ->
+%% Mix of valid + invalid cipher names: rejected up front, naming the bad one
+t_cipher_partial_invalid_returns_error(Config) ->
     ConnOpts = (?conn_opts(Config))#{
         cipher_suites => [
             <<"INVALID_CIPHER">>,
@@ -1050,9 +2134,10 @@ t_cipher_partial_invalid_skips_bad(Config) ->
             <<"ANOTHER_BAD_ONE">>
         ]
     },
-    {ok, Client} = greptimedb_rs:start_client(ConnOpts),
-    ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client, <<"SELECT 1">>)),
-    ok = greptimedb_rs:stop_client(Client).
+    {error, Reason} = greptimedb_rs:start_client(ConnOpts),
+    ReasonBin = reason_to_binary(Reason),
+    ?assertNotEqual(nomatch, binary:match(ReasonBin, <<"tls_config,cipher_suites">>)),
+    ?assertNotEqual(nomatch, binary:match(ReasonBin, <<"INVALID_CIPHER">>)).
 
 %% FIPS status should return a boolean
 t_fips_status_returns_boolean(_Config) ->
@@ -1062,8 +2147,83 @@ t_fips_status_returns_boolean(_Config) ->
     CachedStatus = greptimedb_rs_nif:cached_fips_status(),
     ?assertEqual(FipsStatus, CachedStatus).
 
+t_fips_mode_required(Config) ->
+    ConnOpts = (?conn_opts(Config))#{fips_mode => required},
+    ?assertMatch(
+        {tls_config, verify, _},
+        start_client_error(ConnOpts#{verify => verify_none})
+    ),
+    ?assertMatch(
+        {tls_config, cipher_suites, _},
+        start_client_error(ConnOpts#{cipher_suites => [<<"TLS_CHACHA20_POLY1305_SHA256">>]})
+    ),
+    ?assertMatch({tls_config, tls, _}, start_client_error(ConnOpts#{tls => false})).
+
+%% Fake the host state through the file the NIF reads it from
+t_fips_mode_auto(Config) ->
+    File = filename:join(?config(priv_dir, Config), "fips_enabled"),
+    ok = os:putenv("GREPTIMEDB_RS_FIPS_ENABLED_FILE", File),
+    ConnOpts = (?conn_opts(Config))#{verify => verify_none},
+    try
+        ok = file:write_file(File, <<"1\n">>),
+        ?assert(greptimedb_rs_nif:fips_status()),
+        ?assertMatch({tls_config, verify, _}, start_client_error(ConnOpts)),
+        ?assertMatch(
+            {tls_config, cipher_suites, _},
+            start_client_error(
+                (?conn_opts(Config))#{cipher_suites => [<<"TLS_CHACHA20_POLY1305_SHA256">>]}
+            )
+        ),
+        %% Restricted to the approved suites, still connects
+        {ok, Client0} = greptimedb_rs:start_client(?conn_opts(Config)),
+        ?assertMatch({ok, [_ | _]}, greptimedb_rs:query(Client0, <<"SELECT 1">>)),
+        %% The negotiated suite is an approved one, and the provider is reported as is
+        {ok, [#{cipher_suite := Suite, fips := Fips}]} = greptimedb_rs:connection_status(Client0),
+        ?assert(
+            lists:member(Suite, [
+                <<"TLS_AES_256_GCM_SHA384">>,
+                <<"TLS_AES_128_GCM_SHA256">>,
+                <<"TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384">>,
+                <<"TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256">>,
+                <<"TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384">>,
+                <<"TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256">>
+            ])
+        ),
+        ?assert(is_boolean(Fips)),
+        ok = greptimedb_rs:stop_client(Client0),
+        %% Opting out
+        {ok, Client1} = greptimedb_rs:start_client(ConnOpts#{fips_mode => off}),
+        ok = greptimedb_rs:stop_client(Client1),
+
+        ok = file:write_file(File, <<"0\n">>),
+        ?assertNot(greptimedb_rs_nif:fips_status()),
+        {ok, Client2} = greptimedb_rs:start_client(ConnOpts),
+        ok = greptimedb_rs:stop_client(Client2)
+    after
+        os:unsetenv("GREPTIMEDB_RS_FIPS_ENABLED_FILE"),
+        greptimedb_rs_nif:fips_status(),
+        persistent_term:erase({greptimedb_rs_nif, fips_status})
+    end.
+
 %% ================================================================================
 %% Helpers
+
+%% The `{tls_config, Field, Reason}' a connection fails with
+start_client_error(ConnOpts) ->
+    {error, Reason} = greptimedb_rs:start_client(ConnOpts),
+    find_tls_config_error(Reason).
+
+find_tls_config_error({tls_config, _, _} = Error) ->
+    Error;
+find_tls_config_error(Term) when is_tuple(Term) ->
+    find_tls_config_error(tuple_to_list(Term));
+find_tls_config_error([H | T]) ->
+    case find_tls_config_error(H) of
+        undefined -> find_tls_config_error(T);
+        Error -> Error
+    end;
+find_tls_config_error(_) ->
+    undefined.
 %% ================================================================================
 
 get_host_addr(Env) ->
@@ -1072,6 +2232,93 @@ get_host_addr(Env) ->
         Host -> iolist_to_binary(Host)
     end.
 
+%% Minimal HTTP CONNECT / SOCKS5 proxy stand-in, reporting every tunnel opened as
+%% `{proxied, Kind, Target}' to the calling process. Returns the port it listens on.
+start_proxy(Kind, Auth) ->
+    Parent = self(),
+    {ok, LSock} = gen_tcp:listen(0, [binary, {active, false}, {ip, {127, 0, 0, 1}}]),
+    {ok, Port} = inet:port(LSock),
+    spawn_link(fun() -> proxy_accept(Kind, Auth, LSock, Parent) end),
+    Port.
+
+proxy_accept(Kind, Auth, LSock, Parent) ->
+    case gen_tcp:accept(LSock) of
+        {ok, Sock} ->
+            Handler = spawn(fun() ->
+                receive
+                    go -> proxy_handle(Kind, Auth, Sock, Parent)
+                end
+            end),
+            ok = gen_tcp:controlling_process(Sock, Handler),
+            Handler ! go,
+            proxy_accept(Kind, Auth, LSock, Parent);
+        {error, _} ->
+            ok
+    end.
+
+proxy_handle(http, Auth, Sock, Parent) ->
+    Head = recv_head(Sock, <<>>),
+    [<<"CONNECT ", Request/binary>> | Headers] = binary:split(Head, <<"\r\n">>, [global]),
+    [Target, _Version] = binary:split(Request, <<" ">>),
+    Authorized =
+        case Auth of
+            undefined ->
+                true;
+            {User, Password} ->
+                Credentials = base64:encode(<<User/binary, ":", Password/binary>>),
+                lists:member(<<"Proxy-Authorization: Basic ", Credentials/binary>>, Headers)
+        end,
+    case Authorized of
+        true ->
+            Upstream = connect_target(Target),
+            ok = gen_tcp:send(Sock, <<"HTTP/1.1 200 Connection established\r\n\r\n">>),
+            Parent ! {proxied, http, Target},
+            relay(Sock, Upstream);
+        false ->
+            gen_tcp:send(Sock, <<"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n">>),
+            gen_tcp:close(Sock)
+    end;
+proxy_handle(socks5, undefined, Sock, Parent) ->
+    {ok, <<5, NMethods>>} = gen_tcp:recv(Sock, 2),
+    {ok, _Methods} = gen_tcp:recv(Sock, NMethods),
+    ok = gen_tcp:send(Sock, <<5, 0>>),
+    {ok, <<5, 1, 0, 3, Len>>} = gen_tcp:recv(Sock, 5),
+    {ok, <<Host:Len/binary, Port:16>>} = gen_tcp:recv(Sock, Len + 2),
+    Target = <<Host/binary, ":", (integer_to_binary(Port))/binary>>,
+    Upstream = connect_target(Target),
+    ok = gen_tcp:send(Sock, <<5, 0, 0, 1, 0, 0, 0, 0, 0, 0>>),
+    Parent ! {proxied, socks5, Target},
+    relay(Sock, Upstream).
+
+recv_head(Sock, Acc) ->
+    case binary:split(Acc, <<"\r\n\r\n">>) of
+        [Head, _] ->
+            Head;
+        _ ->
+            {ok, Data} = gen_tcp:recv(Sock, 0),
+            recv_head(Sock, <<Acc/binary, Data/binary>>)
+    end.
+
+connect_target(Target) ->
+    [Host, Port] = binary:split(Target, <<":">>),
+    {ok, Upstream} = gen_tcp:connect(
+        binary_to_list(Host), binary_to_integer(Port), [binary, {active, false}]
+    ),
+    Upstream.
+
+relay(Downstream, Upstream) ->
+    spawn(fun() -> pump(Upstream, Downstream) end),
+    pump(Downstream, Upstream).
+
+pump(From, To) ->
+    case gen_tcp:recv(From, 0) of
+        {ok, Data} ->
+            _ = gen_tcp:send(To, Data),
+            pump(From, To);
+        {error, _} ->
+            gen_tcp:close(To)
+    end.
+
 reason_to_binary(Reason) when is_binary(Reason) ->
     Reason;
 reason_to_binary(Reason) when is_list(Reason) ->